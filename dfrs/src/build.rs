@@ -0,0 +1,97 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::compile::CompiledLine;
+use crate::send::compress;
+use crate::utility::hash;
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Manifest {
+    pub templates: Vec<ManifestEntry>
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ManifestEntry {
+    pub name: String,
+    pub json: String,
+    pub template: String,
+    pub hash: String
+}
+
+impl Manifest {
+    pub fn load(path: &Path) -> Option<Manifest> {
+        let data = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&data).ok()
+    }
+}
+
+/// Writes every line as raw json and as a gzip+base64 template string, together with a manifest.
+pub fn build(code: Vec<CompiledLine>, out: &Path) -> io::Result<Manifest> {
+    fs::create_dir_all(out)?;
+
+    let manifest_path = out.join("manifest.json");
+    if let Some(old) = Manifest::load(&manifest_path) {
+        for entry in old.templates {
+            let _ = fs::remove_file(out.join(entry.json));
+            let _ = fs::remove_file(out.join(entry.template));
+        }
+    }
+
+    let mut manifest = Manifest::default();
+    let mut used = HashSet::new();
+    for line in code {
+        let file_name = unique_file_name(&line.name, &mut used);
+        let json = format!("{file_name}.json");
+        let template = format!("{file_name}.txt");
+
+        fs::write(out.join(&json), &line.code)?;
+        fs::write(out.join(&template), compress(line.code.clone()))?;
+
+        let hash = line.hash();
+        manifest.templates.push(ManifestEntry { name: line.name, json, template, hash });
+    }
+
+    let data = serde_json::to_string_pretty(&manifest).expect("Failed to serialize manifest");
+    fs::write(manifest_path, data)?;
+    Ok(manifest)
+}
+
+fn file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c.to_ascii_lowercase() } else { '_' })
+        .collect()
+}
+
+/// Names that map to the same file name, like "Foo" and "foo", get a short hash of the name appended
+fn unique_file_name(name: &str, used: &mut HashSet<String>) -> String {
+    let base = file_name(name);
+    let mut candidate = base.clone();
+    if used.contains(&candidate) {
+        candidate = format!("{base}-{}", &hash(name)[..8]);
+    }
+    let mut index = 2;
+    while used.contains(&candidate) {
+        candidate = format!("{base}-{}-{index}", &hash(name)[..8]);
+        index += 1;
+    }
+    used.insert(candidate.clone());
+    candidate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colliding_names_get_unique_file_names() {
+        let mut used = HashSet::new();
+        let names: Vec<String> = ["Foo", "foo", "a b", "a_b", "foo"].iter().map(|name| unique_file_name(name, &mut used)).collect();
+        assert_eq!(names[0], "foo");
+        assert_eq!(names[2], "a_b");
+        assert_eq!(used.len(), names.len());
+    }
+}
\ No newline at end of file
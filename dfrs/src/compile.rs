@@ -2,6 +2,7 @@ use std::fmt;
 use serde::{de, ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer};
 use serde::de::{MapAccess, Visitor};
 use crate::node::{ArgValue, ParticleCluster, ParticleData, ProcessNode, StartNode};
+use crate::utility::hash;
 use crate::{node::{ActionNode, ActionType, CallNode, ConditionalNode, ConditionalType, EventNode, Expression, FileNode, FunctionNode, RepeatNode}, token::{get_type_str, Selector}};
 
 pub fn compile(node: FileNode, debug: bool) -> Vec<CompiledLine> {
@@ -209,6 +210,8 @@ fn expression_node(node: Expression) -> Option<Vec<Block>> {
         Expression::Call { node } => Some(vec![call_node(node)]),
         Expression::Start { node } => Some(vec![start_node(node)]),
         Expression::Repeat { node } => Some(repeat_node(node)),
+        Expression::Assign { node } => Some(node.actions.into_iter().map(action_node).collect()),
+        Expression::Group { node } => Some(node.expressions.into_iter().filter_map(|expression| expression_node(expression.node)).flatten().collect()),
         Expression::Variable { .. } => None,
     }
 }
@@ -582,6 +585,15 @@ fn arg_val_from_arg(arg: crate::node::Arg, node_name: String, block: String) ->
         ArgValue::Item { item } => {
             Some( Arg { item: ArgItem { data: ArgValueData::Item { item }, id: String::from("item") }, slot: arg.index } )
         }
+        // vehicles and entity types are given to DF as the item that places them
+        ArgValue::Vehicle { vehicle } => {
+            let item = format!("{{Count:1b,id:\"minecraft:{vehicle}\"}}");
+            Some( Arg { item: ArgItem { data: ArgValueData::Item { item }, id: String::from("item") }, slot: arg.index } )
+        }
+        ArgValue::EntityType { entity_type } => {
+            let item = format!("{{Count:1b,id:\"minecraft:{entity_type}_spawn_egg\"}}");
+            Some( Arg { item: ArgItem { data: ArgValueData::Item { item }, id: String::from("item") }, slot: arg.index } )
+        }
         ArgValue::Tag { tag, value, definition, .. } => {
             let value = match value.as_ref() {
                 ArgValue::Text { text } => text.clone(),
@@ -1375,4 +1387,11 @@ impl Serialize for Location {
 pub struct CompiledLine {
     pub name: String,
     pub code: String
+}
+
+impl CompiledLine {
+    /// Identifies the code of the line, used to tell which lines changed since they were last built or sent
+    pub fn hash(&self) -> String {
+        hash(&self.code)
+    }
 }
\ No newline at end of file
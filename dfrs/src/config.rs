@@ -8,20 +8,95 @@ pub struct Config {
     #[serde(default)]
     pub sending: Sending,
     #[serde(default)]
-    pub debug: Debug
+    pub debug: Debug,
+    #[serde(default)]
+    pub watch: Watch
 }
 
 #[derive(Deserialize, Serialize, Debug)]
 pub struct Sending {
-    pub api: SendApi
+    pub api: SendApi,
+    #[serde(default)]
+    pub codeclient: CodeClientConnection,
+    #[serde(default)]
+    pub recode: RecodeConnection
 }
 
 impl Default for Sending {
     fn default() -> Self {
-        Sending { api: SendApi::CodeClient }
+        Sending { api: SendApi::CodeClient, codeclient: CodeClientConnection::default(), recode: RecodeConnection::default() }
     }
 }
 
+/// Timeouts are in milliseconds
+#[derive(Deserialize, Serialize, Debug)]
+pub struct CodeClientConnection {
+    #[serde(default = "default_host")]
+    pub host: String,
+    #[serde(default = "default_codeclient_port")]
+    pub port: u16,
+    #[serde(default = "default_connect_timeout")]
+    pub connect_timeout: u64,
+    #[serde(default = "default_read_timeout")]
+    pub read_timeout: u64
+}
+
+impl Default for CodeClientConnection {
+    fn default() -> Self {
+        CodeClientConnection {
+            host: default_host(),
+            port: default_codeclient_port(),
+            connect_timeout: default_connect_timeout(),
+            read_timeout: default_read_timeout()
+        }
+    }
+}
+
+/// Timeouts are in milliseconds
+#[derive(Deserialize, Serialize, Debug)]
+pub struct RecodeConnection {
+    #[serde(default = "default_host")]
+    pub host: String,
+    #[serde(default = "default_recode_port")]
+    pub port: u16,
+    #[serde(default = "default_connect_timeout")]
+    pub connect_timeout: u64,
+    #[serde(default = "default_read_timeout")]
+    pub read_timeout: u64
+}
+
+impl Default for RecodeConnection {
+    fn default() -> Self {
+        RecodeConnection {
+            host: default_host(),
+            port: default_recode_port(),
+            connect_timeout: default_connect_timeout(),
+            read_timeout: default_read_timeout()
+        }
+    }
+}
+
+fn default_host() -> String {
+    "localhost".into()
+}
+
+fn default_codeclient_port() -> u16 {
+    31375
+}
+
+fn default_recode_port() -> u16 {
+    31372
+}
+
+fn default_connect_timeout() -> u64 {
+    2000
+}
+
+/// Waiting for authorization includes the time it takes to run /auth in game, so this is generous
+fn default_read_timeout() -> u64 {
+    30000
+}
+
 #[derive(Deserialize, Serialize, Debug)]
 #[serde(rename_all="lowercase")]
 pub enum SendApi {
@@ -29,6 +104,24 @@ pub enum SendApi {
     Recode
 }
 
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Watch {
+    #[serde(default = "bool::default")]
+    pub send: bool,
+    #[serde(default = "default_watch_interval")]
+    pub interval: u64
+}
+
+fn default_watch_interval() -> u64 {
+    500
+}
+
+impl Default for Watch {
+    fn default() -> Self {
+        Watch { send: false, interval: default_watch_interval() }
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Default)]
 pub struct Debug {
     #[serde(default = "bool::default")]
@@ -46,4 +139,29 @@ impl Config {
         let data = toml::to_string(self).expect("Failed to create new config");
         std::fs::write(path, data).expect("Failed to save new config");
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn connections_fall_back_to_defaults() {
+        let config: Config = toml::from_str("[sending]\napi = \"recode\"\n\n[sending.codeclient]\nport = 1234").expect("Failed to parse config");
+        assert!(matches!(config.sending.api, SendApi::Recode));
+        assert_eq!(config.sending.codeclient.host, "localhost");
+        assert_eq!(config.sending.codeclient.port, 1234);
+        assert_eq!(config.sending.recode.host, "localhost");
+        assert_eq!(config.sending.recode.port, 31372);
+        assert_eq!(config.sending.recode.connect_timeout, 2000);
+        assert_eq!(config.sending.recode.read_timeout, 30000);
+    }
+
+    #[test]
+    fn empty_config_sends_with_codeclient() {
+        let config: Config = toml::from_str("").expect("Failed to parse config");
+        assert!(matches!(config.sending.api, SendApi::CodeClient));
+        assert_eq!(config.sending.codeclient.port, 31375);
+        assert_eq!(config.watch.interval, 500);
+    }
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::io::{Cursor, Read};
 use base64::Engine;
 use base64::prelude::BASE64_STANDARD;
@@ -27,10 +27,63 @@ fn decompress(compressed_code: &str) -> String {
     decompressed_data
 }
 
+fn is_block(blocks: &[Block], index: usize, prefix: &str) -> bool {
+    blocks.get(index).is_some_and(|block| block.block.as_ref().is_some_and(|name| name.starts_with(prefix)))
+}
+
+fn matching_close(blocks: &[Block], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (index, block) in blocks.iter().enumerate().skip(open) {
+        if block.id != "bracket" {
+            continue;
+        }
+        match block.direct.as_deref() {
+            Some("open") => depth += 1,
+            Some("close") => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(index);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Returns the index of the last block of the conditional starting at `start`, including its else block
+fn conditional_end(blocks: &[Block], start: usize) -> Option<usize> {
+    let close = matching_close(blocks, start + 1)?;
+    if is_block(blocks, close + 1, "else") {
+        return matching_close(blocks, close + 2);
+    }
+    Some(close)
+}
+
+/// Finds else blocks that only contain a conditional so they can be written as `else if`,
+/// returns them and the brackets around their contents that are left out
+fn find_else_ifs(blocks: &[Block]) -> (HashSet<usize>, HashSet<usize>) {
+    let mut else_ifs = HashSet::new();
+    let mut skipped_brackets = HashSet::new();
+    for index in 0..blocks.len() {
+        if !is_block(blocks, index, "else") {
+            continue;
+        }
+        let Some(close) = matching_close(blocks, index + 1) else { continue };
+        if is_block(blocks, index + 2, "if_") && conditional_end(blocks, index + 2) == Some(close - 1) {
+            else_ifs.insert(index);
+            skipped_brackets.insert(index + 1);
+            skipped_brackets.insert(close);
+        }
+    }
+    (else_ifs, skipped_brackets)
+}
+
 pub struct Decompiler {
     indentation: i32,
     action_dump: ActionDump,
     vars: HashMap<String, String>,
+    else_if: bool,
 }
 
 impl Decompiler {
@@ -40,6 +93,7 @@ impl Decompiler {
             indentation: 0,
             action_dump: ActionDump::new(&ad),
             vars: HashMap::new(),
+            else_if: false,
         }
     }
 
@@ -102,7 +156,15 @@ impl Decompiler {
         vars.sort();
         vars.dedup();
 
-        for block in line.blocks {
+        let (else_ifs, skipped_brackets) = find_else_ifs(&line.blocks);
+        for (index, block) in line.blocks.into_iter().enumerate() {
+            if skipped_brackets.contains(&index) {
+                continue;
+            }
+            if else_ifs.contains(&index) {
+                self.else_if = true;
+                continue;
+            }
             match block.id.as_str() {
                 "block" => {
                     self.decompile_block(block, vars.clone());
@@ -338,7 +400,7 @@ impl Decompiler {
         self.add(&format!("{prefix}{selector}.{}({});", name, self.decompile_params(block, action)))
     }
 
-    fn decompile_conditional(&self, block: Block, conditional_type: ConditionalType) {
+    fn decompile_conditional(&mut self, block: Block, conditional_type: ConditionalType) {
         let name = to_dfrs_name(&block.action.clone().unwrap());
         let action = match conditional_type {
             ConditionalType::Player => self.action_dump.player_conditionals.get(name.clone()),
@@ -361,7 +423,8 @@ impl Decompiler {
         } else {
             ""
         };
-        self.add(&format!("{prefix} {inverted}{selector}{}({}) {{", name, self.decompile_params(block, action)))
+        let else_prefix = if std::mem::take(&mut self.else_if) { "else " } else { "" };
+        self.add(&format!("{else_prefix}{prefix} {inverted}{selector}{}({}) {{", name, self.decompile_params(block, action)))
     }
 
     fn decompile_repeat(&self, block: Block) {
@@ -383,9 +446,10 @@ impl Decompiler {
         let action = &Action {
             df_name: "internal".into(),
             dfrs_name: "internal".into(),
-            args,
+            overloads: vec![args],
             tags: vec![],
-            has_conditional_arg: false
+            has_conditional_arg: false,
+            return_type: None
         };
         if block.args.is_some() && block.args.clone().unwrap().items.len() > 0 {
             self.add(&format!("call(\"{}\", {});", to_dfrs_name(&block.data.clone().unwrap()), self.decompile_params(block, action)));
@@ -8,8 +8,7 @@ use super::{ArgType, DefinedArg, DefinedTag};
 pub struct RawActionDump {
     pub codeblocks: Vec<ADCodeBlock>,
     pub actions: Vec<ADAction>,
-    #[serde(skip)]
-    pub game_value_categories: String,
+    pub game_value_categories: Vec<ADGameValueCategory>,
     pub game_values: Vec<ADGameValue>,
     #[serde(skip)]
     pub particle_categories: String,
@@ -29,7 +28,7 @@ pub struct ADParticle {
     particle: String,
     icon: ADIcon,
     category: Option<String>,
-    fields: Vec<String>
+    pub fields: Vec<String>
 }
 
 impl DFRSValue for ADParticle {
@@ -117,7 +116,9 @@ pub struct ADIcon {
     pub arguments: Vec<ADArgument>,
     #[serde(default="default_vec_return", rename="returnValues")]
     pub return_values: Vec<ADReturnValue>,
-    pub return_type: Option<String>
+    pub return_type: Option<String>,
+    #[serde(default="default_bool")]
+    pub cancellable: bool
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -144,6 +145,14 @@ pub struct ADReturnValue {
     pub description: Vec<String>,
 }
 
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all="camelCase")]
+pub struct ADGameValueCategory {
+    pub identifier: String,
+    pub gui_slot: i32,
+    pub icon: ADIcon
+}
+
 #[derive(Deserialize, Clone, Debug)]
 #[serde(rename_all="camelCase")]
 pub struct ADGameValue {
@@ -188,13 +197,16 @@ pub struct Action {
     pub dfrs_name: String,
     pub df_name: String,
     pub has_conditional_arg: bool,
-    pub args: Vec<DefinedArg>,
-    pub tags: Vec<DefinedTag>
+    /// Every argument layout the action accepts, actions with `OR` in their arguments have more than one
+    pub overloads: Vec<Vec<DefinedArg>>,
+    pub tags: Vec<DefinedTag>,
+    /// Type of the value set variable actions store in their first argument
+    pub return_type: Option<ArgType>
 }
 
 impl Action {
     pub fn new(dfrs_name: String, df_name: &str, args: Vec<DefinedArg>, tags: Vec<DefinedTag>, has_conditional_arg: bool) -> Action {
-        Action {dfrs_name, df_name: df_name.to_owned(), args, tags, has_conditional_arg}
+        Action {dfrs_name, df_name: df_name.to_owned(), overloads: vec![args], tags, has_conditional_arg, return_type: None}
     }
 }
 
@@ -223,81 +235,38 @@ pub fn get_actions(action_dump: &RawActionDump, block: &str) -> Vec<Action> {
 }
 
 pub fn get_action(action: &ADAction) -> Action {
-    let mut args: Vec<DefinedArg> = vec![];
-    let mut is_or = false;
-    let mut index = 0;
-    let mut index_after_or = 0;
-    let mut args_before_or= 0;
-    let mut or_index= 0;
-    let mut current_args: Vec<DefinedArg> = vec![];
-
-    for arg in &action.icon.arguments {
+    // empty lines split the arguments into groups, a group can have alternatives separated by OR
+    let mut groups: Vec<Vec<Vec<DefinedArg>>> = vec![];
+    let mut alternatives: Vec<Vec<DefinedArg>> = vec![vec![]];
+    let mut after_or = false;
+
+    for (index, arg) in action.icon.arguments.iter().enumerate() {
         let arg_type = match &arg.arg_type as &str {
-            "NUMBER" => ArgType::NUMBER,
-            "COMPONENT" => ArgType::TEXT,
-            "TEXT" => ArgType::STRING,
-            "LOCATION" => ArgType::LOCATION,
-            "POTION" => ArgType::POTION,
-            "SOUND" => ArgType::SOUND,
-            "VECTOR" => ArgType::VECTOR,
-            "PARTICLE" => ArgType::PARTICLE,
-            "LIST" => ArgType::VARIABLE,
-            "DICT" => ArgType::VARIABLE,
-            "VARIABLE" => ArgType::VARIABLE,
-            "ITEM" => ArgType::ITEM,
-            "BLOCK" => ArgType::ITEM,
-            "BLOCK_TAG" => ArgType::STRING,
-            "PROJECTILE" => ArgType::ITEM,
-            "SPAWN_EGG" => ArgType::ITEM,
-            "ANY_TYPE" => ArgType::ANY,
-            "NONE" => ArgType::EMPTY,
-
-            //TODO all below
-            "VEHICLE" => ArgType::EMPTY,
-            "ENTITY_TYPE" => ArgType::EMPTY,
             "OR" => {
-                or_index = index - 1;
-                index_after_or = 0;
-                args_before_or = current_args.len();
-                is_or = true;
+                alternatives.push(vec![]);
+                after_or = true;
                 continue;
             },
             "" => {
-                if is_or {
-                    return Action::new(action.name.clone() + "-NotYetSupported", &action.name, vec![], vec![], action.sub_action_blocks.is_some() && !action.sub_action_blocks.clone().unwrap().is_empty());
+                let before_or = action.icon.arguments[index + 1..].iter().find(|arg| !arg.arg_type.is_empty()).is_some_and(|arg| arg.arg_type == "OR");
+                if !after_or && !before_or {
+                    groups.push(alternatives);
+                    alternatives = vec![vec![]];
                 }
-                for arg in current_args {
-                    args.push(arg);
-                }
-                current_args = vec![];
-                index = 0;
                 continue;
             },
-            
-            _ => panic!("Unknown arg type: {}", arg.arg_type)
+            other => to_arg_types(other).unwrap_or_else(|| panic!("Unknown arg type: {}", other))
         };
-        index += 1;
-
-        if is_or {
-            if index_after_or > args_before_or - 1 {
-                let new_arg = DefinedArg::new(arg.description.first().expect("No description"), vec![arg_type], true, arg.plural);
-                current_args.push(new_arg);
-            } else {
-                current_args.get_mut(index_after_or).unwrap().arg_types.push(arg_type);
-            }
-            index_after_or += 1;
-        } else {
-            let new_arg = DefinedArg::new(arg.description.first().expect("No description"), vec![arg_type], arg.optional, arg.plural);
-            current_args.push(new_arg);
-        }
-    }
-    if is_or && or_index != current_args.len() && or_index > current_args.len() - or_index {
-        for i in (current_args.len() - or_index)..=or_index {
-            current_args.get_mut(i).unwrap().optional = true;
-        }
+        after_or = false;
+
+        let new_arg = DefinedArg::new(arg.description.first().expect("No description"), arg_type, arg.optional, arg.plural);
+        alternatives.last_mut().unwrap().push(new_arg);
     }
-    for arg in current_args {
-        args.push(arg);
+    groups.push(alternatives);
+
+    let mut overloads: Vec<Vec<DefinedArg>> = vec![vec![]];
+    for group in groups {
+        overloads = overloads.iter().flat_map(|overload| group.iter().map(move |alternative| [overload.clone(), alternative.clone()].concat())).collect();
     }
 
     let mut tags = vec![];
@@ -313,7 +282,46 @@ pub fn get_action(action: &ADAction) -> Action {
     }
 
     let name = to_dfrs_name(&action.name);
-    Action::new(name, &action.name, args, tags, action.sub_action_blocks.is_some() && !action.sub_action_blocks.clone().unwrap().is_empty())
+    let mut result = Action::new(name, &action.name, vec![], tags, action.sub_action_blocks.is_some() && !action.sub_action_blocks.clone().unwrap().is_empty());
+    result.overloads = overloads;
+    result.return_type = action.icon.return_values.first().and_then(|value| to_arg_type(&value.return_type));
+    result
+}
+
+/// Maps the type names used in the action dump to the types of arguments
+pub fn to_arg_type(name: &str) -> Option<ArgType> {
+    let arg_type = match name {
+        "NUMBER" => ArgType::NUMBER,
+        "COMPONENT" => ArgType::TEXT,
+        "TEXT" => ArgType::STRING,
+        "LOCATION" => ArgType::LOCATION,
+        "POTION" => ArgType::POTION,
+        "SOUND" => ArgType::SOUND,
+        "VECTOR" => ArgType::VECTOR,
+        "PARTICLE" => ArgType::PARTICLE,
+        "LIST" => ArgType::VARIABLE,
+        "DICT" => ArgType::VARIABLE,
+        "VARIABLE" => ArgType::VARIABLE,
+        "ITEM" => ArgType::ITEM,
+        "BLOCK" => ArgType::ITEM,
+        "BLOCK_TAG" => ArgType::STRING,
+        "PROJECTILE" => ArgType::ITEM,
+        "SPAWN_EGG" => ArgType::ITEM,
+        "ANY_TYPE" => ArgType::ANY,
+        "NONE" => ArgType::EMPTY,
+        "VEHICLE" => ArgType::VEHICLE,
+        "ENTITY_TYPE" => ArgType::ENTITY_TYPE,
+        _ => return None
+    };
+    Some(arg_type)
+}
+
+/// The types of arguments a parameter accepts, spawn egg parameters also accept an entity type
+fn to_arg_types(name: &str) -> Option<Vec<ArgType>> {
+    match name {
+        "SPAWN_EGG" => Some(vec![ArgType::ITEM, ArgType::ENTITY_TYPE]),
+        other => to_arg_type(other).map(|arg_type| vec![arg_type])
+    }
 }
 
 trait DFRSValue {
@@ -337,6 +345,10 @@ impl<T> ValueList<T> where T: DFRSValue {
     pub fn all(&self) -> &Vec<T> {
         &self.values
     }
+
+    pub fn names(&self) -> Vec<String> {
+        self.values.iter().map(|value| value.dfrs_name()).collect()
+    }
 }
 
 #[derive(Debug)]
@@ -366,11 +378,12 @@ impl ActionDump {
         let actions  = get_actions(&action_dump, "START PROCESS");
         let action = actions.get(0).unwrap();
         let start_process_action = Action {
-            args: action.args.clone(),
+            overloads: action.overloads.clone(),
             df_name: action.df_name.clone(),
             dfrs_name: action.dfrs_name.clone(),
             tags: action.tags.clone(),
-            has_conditional_arg: action.has_conditional_arg.clone()
+            has_conditional_arg: action.has_conditional_arg,
+            return_type: action.return_type.clone()
         };
 
         ActionDump {
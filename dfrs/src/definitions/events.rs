@@ -1,10 +1,95 @@
 use crate::definitions::action_dump::{RawActionDump};
+use crate::token::Selector;
 use crate::utility::to_dfrs_name;
 
+/// Selectors that only exist in some events, by the dfrs name of the events that provide them.
+/// The action dump doesn't list which targets an event has, so this follows the target descriptions of the events in game.
+/// Events that are missing here are not checked at all, so a test makes sure every event of the action dump is listed.
+static EVENT_SELECTORS: &[(&str, &[Selector])] = &[
+    ("join", &[]),
+    ("leave", &[]),
+    ("rightClick", &[]),
+    ("leftClick", &[]),
+    ("sneak", &[]),
+    ("unsneak", &[]),
+    ("jump", &[]),
+    ("respawn", &[]),
+    ("swapHands", &[]),
+    ("changeSlot", &[]),
+    ("loop", &[]),
+    ("command", &[]),
+    ("packLoad", &[]),
+    ("packDecline", &[]),
+    ("placeBlock", &[]),
+    ("breakBlock", &[]),
+    ("walk", &[]),
+    ("startSprint", &[]),
+    ("stopSprint", &[]),
+    ("startFly", &[]),
+    ("stopFly", &[]),
+    ("riptide", &[]),
+    ("dismount", &[]),
+    ("horseJump", &[]),
+    ("vehicleJump", &[]),
+    ("clickMenuSlot", &[]),
+    ("clickInvSlot", &[]),
+    ("pickUpItem", &[]),
+    ("dropItem", &[]),
+    ("consume", &[]),
+    ("breakItem", &[]),
+    ("closeInv", &[]),
+    ("fish", &[]),
+    ("playerHeal", &[]),
+    ("playerResurrect", &[]),
+    ("cloudImbuePlayer", &[]),
+    ("clickEntity", &[Selector::Victim]),
+    ("clickPlayer", &[Selector::Victim]),
+    ("tameEntity", &[Selector::Victim]),
+    ("killPlayer", &[Selector::Killer, Selector::Victim]),
+    ("killMob", &[Selector::Killer, Selector::Victim]),
+    ("mobKillPlayer", &[Selector::Killer]),
+    ("death", &[Selector::Killer, Selector::Damager]),
+    ("playerTakeDmg", &[Selector::Damager]),
+    ("playerDmgPlayer", &[Selector::Damager, Selector::Victim]),
+    ("damageEntity", &[Selector::Damager, Selector::Victim]),
+    ("entityDmgPlayer", &[Selector::Damager]),
+    ("projDmgPlayer", &[Selector::Damager, Selector::Shooter, Selector::Projectile]),
+    ("shootBow", &[Selector::Shooter, Selector::Projectile]),
+    ("shootProjectile", &[Selector::Shooter, Selector::Projectile]),
+    ("projHit", &[Selector::Shooter, Selector::Projectile, Selector::Victim]),
+    ("entityDmgEntity", &[Selector::Damager, Selector::Victim]),
+    ("entityKillEntity", &[Selector::Killer, Selector::Victim]),
+    ("entityDmg", &[Selector::Damager]),
+    ("projDmgEntity", &[Selector::Damager, Selector::Shooter, Selector::Projectile, Selector::Victim]),
+    ("projKillEntity", &[Selector::Killer, Selector::Shooter, Selector::Projectile, Selector::Victim]),
+    ("entityDeath", &[Selector::Killer, Selector::Damager]),
+    ("vehicleDamage", &[Selector::Damager]),
+    ("entityExplode", &[]),
+    ("entityResurrect", &[]),
+    ("entityHeal", &[]),
+    ("regrowWool", &[]),
+    ("blockFall", &[]),
+    ("fallingBlockLand", &[])
+];
+
+/// Whether the selector only exists in some events, like killer
+pub fn is_event_selector(selector: &Selector) -> bool {
+    matches!(selector, Selector::Killer | Selector::Damager | Selector::Shooter | Selector::Victim | Selector::Projectile)
+}
+
+fn event_selectors(dfrs_name: &str) -> Option<Vec<Selector>> {
+    EVENT_SELECTORS.iter()
+        .find(|(event, _)| *event == dfrs_name)
+        .map(|(_, selectors)| selectors.to_vec())
+}
+
 #[derive(Debug)]
 pub struct Event {
     pub dfrs_name: String,
-    pub df_name: String
+    pub df_name: String,
+    /// The event specific selectors the event provides, None if they are not known
+    pub selectors: Option<Vec<Selector>>,
+    pub cancellable: bool
 }
 
 #[derive(Debug)]
@@ -20,6 +105,8 @@ impl PlayerEvents {
                 let name = to_dfrs_name(&entry.name.clone());
                 events.push(Event {
                     df_name: entry.name.clone(),
+                    selectors: event_selectors(&name),
+                    cancellable: entry.icon.cancellable,
                     dfrs_name: name
                 })
             }
@@ -49,6 +136,8 @@ impl EntityEvents {
                 let name: String = to_dfrs_name(&entry.name.clone());
                 events.push(Event {
                     df_name: entry.name.clone(),
+                    selectors: event_selectors(&name),
+                    cancellable: entry.icon.cancellable,
                     dfrs_name: name
                 })
             }
@@ -63,4 +152,21 @@ impl EntityEvents {
     pub fn all(&self) -> &Vec<Event> {
         &self.events
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_event_has_known_selectors() {
+        let action_dump = RawActionDump::load();
+        let player_events = PlayerEvents::new(&action_dump);
+        let entity_events = EntityEvents::new(&action_dump);
+        let missing: Vec<&String> = player_events.all().iter().chain(entity_events.all())
+            .filter(|event| event.selectors.is_none())
+            .map(|event| &event.dfrs_name)
+            .collect();
+        assert!(missing.is_empty(), "Events missing from EVENT_SELECTORS: {missing:?}");
+    }
 }
\ No newline at end of file
@@ -4,14 +4,16 @@ use crate::utility::to_dfrs_name;
 
 #[derive(Debug)]
 pub struct GameValues {
-    game_values: Vec<GameValue>
+    game_values: Vec<GameValue>,
+    categories: Vec<String>
 }
 
 #[derive(Debug)]
 pub struct GameValue {
     pub df_name: String,
     pub dfrs_name: String,
-    pub value_type: ArgType
+    pub value_type: ArgType,
+    pub category: String
 }
 
 impl GameValues {
@@ -33,12 +35,17 @@ impl GameValues {
             let new_value = GameValue {
                 df_name: game_value.icon.name.clone(),
                 dfrs_name: to_dfrs_name(&game_value.icon.name.clone()),
-                value_type
+                value_type,
+                category: game_value.category
             };
             game_values.push(new_value);
         }
 
-        GameValues {game_values}
+        let mut categories = action_dump.game_value_categories.clone();
+        categories.sort_by_key(|category| category.gui_slot);
+        let categories = categories.into_iter().map(|category| category.identifier).collect();
+
+        GameValues {game_values, categories}
     }
 
     pub fn get(&self, dfrs_name: String) -> Option<&GameValue> {
@@ -48,4 +55,9 @@ impl GameValues {
     pub fn all(&self) -> &Vec<GameValue> {
         &self.game_values
     }
+
+    /// Names of the game value categories, in the order DF shows them
+    pub fn categories(&self) -> &Vec<String> {
+        &self.categories
+    }
 }
\ No newline at end of file
@@ -2,6 +2,8 @@ pub mod action_dump;
 pub mod game_values;
 pub mod events;
 
+use crate::token::Type;
+
 #[derive(Clone, Debug)]
 pub struct DefinedArg {
     pub arg_types: Vec<ArgType>,
@@ -28,6 +30,9 @@ pub enum ArgType {
     POTION,
     PARTICLE,
     ITEM,
+    VEHICLE,
+    #[allow(non_camel_case_types)]
+    ENTITY_TYPE,
     TAG,
     VARIABLE,
     GameValue,
@@ -35,6 +40,24 @@ pub enum ArgType {
     ANY
 }
 
+impl From<&Type> for ArgType {
+    fn from(param_type: &Type) -> ArgType {
+        match param_type {
+            Type::String => ArgType::STRING,
+            Type::Text => ArgType::TEXT,
+            Type::Number => ArgType::NUMBER,
+            Type::Location => ArgType::LOCATION,
+            Type::Vector => ArgType::VECTOR,
+            Type::Sound => ArgType::SOUND,
+            Type::Particle => ArgType::PARTICLE,
+            Type::Potion => ArgType::POTION,
+            Type::Item => ArgType::ITEM,
+            Type::Any => ArgType::ANY,
+            Type::Variable | Type::List | Type::Dict => ArgType::VARIABLE
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct DefinedTag {
     pub dfrs_name: String,
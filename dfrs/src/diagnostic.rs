@@ -0,0 +1,243 @@
+use std::cmp;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use colored::Colorize;
+use serde::Serialize;
+
+use crate::token::Position;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all="lowercase")]
+pub enum Severity {
+    Error,
+    Warning
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position
+}
+
+impl Span {
+    pub fn new(start: Position, end: Position) -> Span {
+        Span { start, end }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Label {
+    pub span: Span,
+    pub message: String
+}
+
+/// A single problem found while compiling a file.
+/// Every stage converts its own errors into this, so the cli and the lsp show the same text.
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub span: Option<Span>,
+    pub labels: Vec<Label>,
+    pub help: Option<String>
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: String) -> Diagnostic {
+        Diagnostic { severity: Severity::Error, code, message, span: None, labels: vec![], help: None }
+    }
+
+    pub fn warning(code: &'static str, message: String) -> Diagnostic {
+        Diagnostic { severity: Severity::Warning, code, message, span: None, labels: vec![], help: None }
+    }
+
+    pub fn with_span(mut self, start: Position, end: Position) -> Diagnostic {
+        self.span = Some(Span::new(start, end));
+        self
+    }
+
+    pub fn with_label(mut self, start: Position, end: Position, message: String) -> Diagnostic {
+        self.labels.push(Label { span: Span::new(start, end), message });
+        self
+    }
+
+    pub fn with_help(mut self, help: String) -> Diagnostic {
+        self.help = Some(help);
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// One json object per diagnostic, meant for tools that read `--message-format=json` output line by line.
+    pub fn to_json(&self, file: &Path) -> String {
+        #[derive(Serialize)]
+        struct JsonDiagnostic<'a> {
+            file: String,
+            #[serde(flatten)]
+            diagnostic: &'a Diagnostic
+        }
+
+        serde_json::to_string(&JsonDiagnostic { file: file.to_string_lossy().to_string(), diagnostic: self })
+            .expect("Failed to serialize diagnostic")
+    }
+
+    pub fn render(&self, source: &str) -> String {
+        let mut result = String::new();
+        let header = match self.severity {
+            Severity::Error => format!("error[{}]:", self.code).bright_red(),
+            Severity::Warning => format!("warning[{}]:", self.code).yellow()
+        };
+        result.push_str(&format!("{} {}\n", header, self.message));
+
+        let lines = source.split('\n').collect::<Vec<&str>>();
+        let mut marks = vec![];
+        if let Some(span) = &self.span {
+            marks.push(Mark { span, primary: true, message: None });
+        }
+        for label in &self.labels {
+            marks.push(Mark { span: &label.span, primary: false, message: Some(&label.message) });
+        }
+        marks.retain(|mark| mark.span.start.line >= 1 && mark.span.start.line as usize <= lines.len());
+
+        if !marks.is_empty() {
+            let mut shown = BTreeSet::new();
+            for mark in &marks {
+                let end_line = cmp::min(cmp::max(mark.span.end.line, mark.span.start.line), lines.len() as u32);
+                let (first, last) = if mark.primary {
+                    (mark.span.start.line.saturating_sub(CONTEXT_LINES), end_line + CONTEXT_LINES)
+                } else {
+                    (mark.span.start.line, end_line)
+                };
+                for ln in cmp::max(first, 1)..=cmp::min(last, lines.len() as u32) {
+                    shown.insert(ln);
+                }
+            }
+
+            let gutter = shown.iter().last().unwrap().to_string().chars().count();
+            let bar = "|".bright_black();
+            result.push_str(&format!("{} {}\n", " ".repeat(gutter), bar));
+            let mut previous = None;
+            for ln in shown {
+                if let Some(previous) = previous {
+                    if ln > previous + 1 {
+                        result.push_str(&format!("{}\n", "...".bright_black()));
+                    }
+                }
+                previous = Some(ln);
+
+                let line = lines[(ln - 1) as usize].trim_end_matches('\r');
+                result.push_str(&format!("{} {} {}\n", format!("{:>gutter$}", ln).bright_black(), bar, line));
+                for mark in &marks {
+                    if let Some((from, to)) = mark.columns(ln, line) {
+                        let underline = if mark.primary { "^" } else { "-" }.repeat(to - from);
+                        let underline = if mark.primary { underline.bright_blue() } else { underline.bright_black() };
+                        let mut row = format!("{} {} {}{}", " ".repeat(gutter), bar, " ".repeat(from), underline);
+                        if let Some(message) = mark.message {
+                            if ln == cmp::max(mark.span.end.line, mark.span.start.line) {
+                                row.push_str(&format!(" {}", message));
+                            }
+                        }
+                        result.push_str(&format!("{}\n", row));
+                    }
+                }
+            }
+        }
+        if let Some(help) = &self.help {
+            result.push_str(&format!("{} {}\n", "help:".bright_cyan(), help));
+        }
+        result
+    }
+}
+
+const CONTEXT_LINES: u32 = 1;
+
+struct Mark<'a> {
+    span: &'a Span,
+    primary: bool,
+    message: Option<&'a String>
+}
+
+impl Mark<'_> {
+    /// Returns the 0-based column range to underline on the given line, if the span touches it.
+    fn columns(&self, ln: u32, line: &str) -> Option<(usize, usize)> {
+        let start = &self.span.start;
+        let end = &self.span.end;
+        let end_line = cmp::max(end.line, start.line);
+        if ln < start.line || ln > end_line {
+            return None;
+        }
+        let length = line.chars().count();
+        let indent = line.chars().take_while(|c| c.is_whitespace()).count();
+        if indent == length && ln != start.line && ln != end_line {
+            // Nothing to underline on blank lines inside the span
+            return None;
+        }
+
+        let from = if ln == start.line { start.col.saturating_sub(1) as usize } else { indent };
+        let to = if ln == end_line && end.line == ln {
+            end.col.saturating_sub(1) as usize
+        } else {
+            length
+        };
+        Some((from, cmp::max(to, from + 1)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_contains_the_file_and_every_field() {
+        let diagnostic = Diagnostic::warning("W0201", "Selector 'killer' is not available".into())
+            .with_span(Position::new(2, 3), Position::new(2, 9))
+            .with_label(Position::new(1, 1), Position::new(1, 6), "event declared here".into())
+            .with_help("help text".into());
+        let json: serde_json::Value = serde_json::from_str(&diagnostic.to_json(Path::new("main.dfrs"))).expect("Invalid json");
+        assert_eq!(json["file"], "main.dfrs");
+        assert_eq!(json["severity"], "warning");
+        assert_eq!(json["code"], "W0201");
+        assert_eq!(json["span"]["start"]["line"], 2);
+        assert_eq!(json["labels"][0]["message"], "event declared here");
+        assert_eq!(json["help"], "help text");
+        assert!(!diagnostic.is_error());
+    }
+    #[test]
+    fn render_underlines_the_span_and_labels() {
+        colored::control::set_override(false);
+        let source = "line a: number;\n@join {\n  v.equal(a, \"b\");\n}";
+        let rendered = Diagnostic::error("E0211", "Variable 'a' is set to a value of the wrong type".into())
+            .with_span(Position::new(3, 5), Position::new(3, 10))
+            .with_label(Position::new(1, 1), Position::new(1, 15), "declared as NUMBER here".into())
+            .with_help("the variable is declared as NUMBER but the value is TEXT".into())
+            .render(source);
+        let expected = [
+            "error[E0211]: Variable 'a' is set to a value of the wrong type",
+            "  |",
+            "1 | line a: number;",
+            "  | -------------- declared as NUMBER here",
+            "2 | @join {",
+            "3 |   v.equal(a, \"b\");",
+            "  |     ^^^^^",
+            "4 | }",
+            "help: the variable is declared as NUMBER but the value is TEXT",
+            ""
+        ];
+        assert_eq!(rendered, expected.join("\n"));
+    }
+
+    #[test]
+    fn render_skips_blank_lines_inside_multi_line_spans() {
+        colored::control::set_override(false);
+        let source = "@join {\n  p.teleport(\n\n  );\n}";
+        let rendered = Diagnostic::error("E0204", "Missing argument 'New position'".into())
+            .with_span(Position::new(2, 5), Position::new(4, 4))
+            .render(source);
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines[3..], ["2 |   p.teleport(", "  |     ^^^^^^^^^", "3 | ", "4 |   );", "  |   ^", "5 | }"]);
+    }
+}
\ No newline at end of file
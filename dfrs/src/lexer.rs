@@ -1,3 +1,4 @@
+use crate::diagnostic::Diagnostic;
 use crate::token::{Position, Token, TokenWithPos, KEYWORDS, SELECTORS};
 
 #[derive(Debug)]
@@ -9,6 +10,28 @@ pub enum LexerError {
     UnterminatedVariable { pos: Position }
 }
 
+impl From<LexerError> for Diagnostic {
+    fn from(err: LexerError) -> Diagnostic {
+        match err {
+            LexerError::InvalidNumber { pos } => {
+                Diagnostic::error("E0001", "Invalid number".into()).with_span(pos.clone(), pos)
+            }
+            LexerError::InvalidToken { token, pos } => {
+                Diagnostic::error("E0002", format!("Invalid token '{token}'")).with_span(pos.clone(), pos)
+            }
+            LexerError::UnterminatedString { pos } => {
+                Diagnostic::error("E0003", "Unterminated string".into()).with_span(pos.clone(), pos)
+            }
+            LexerError::UnterminatedText { pos } => {
+                Diagnostic::error("E0004", "Unterminated text".into()).with_span(pos.clone(), pos)
+            }
+            LexerError::UnterminatedVariable { pos } => {
+                Diagnostic::error("E0005", "Unterminated variable".into()).with_span(pos.clone(), pos)
+            }
+        }
+    }
+}
+
 pub struct Lexer {
     char_pos: i32,
     input: String,
@@ -46,13 +69,8 @@ impl Lexer {
         let mut dot_count = 0;
         let start_pos = self.position.clone();
 
-        while self.current_char.is_some() && (self.current_char.unwrap().is_ascii_digit() || self.current_char.unwrap() == '.' || self.current_char.unwrap() == '-') {
+        while self.current_char.is_some() && (self.current_char.unwrap().is_ascii_digit() || self.current_char.unwrap() == '.' || (self.current_char.unwrap() == '-' && num_string.is_empty())) {
             if self.current_char.unwrap() == '.' { dot_count += 1 }
-            if self.current_char.unwrap() == '-' {
-                if !num_string.is_empty() {
-                    return Err(LexerError::InvalidNumber {pos: start_pos});
-                }
-            }
             if dot_count > 1 { return Err(LexerError::InvalidNumber{ pos: self.position.clone() }) }
             num_string.push_str(&self.current_char.unwrap().to_string());
             self.advance();
@@ -62,7 +80,11 @@ impl Lexer {
             return Err(LexerError::InvalidNumber { pos: start_pos })
         }
 
-        Ok(TokenWithPos { token: Token::Number { value: num_string.parse::<f32>().unwrap() }, start_pos, end_pos: self.position.clone()})
+        let value = match num_string.parse::<f32>() {
+            Ok(value) => value,
+            Err(_) => return Err(LexerError::InvalidNumber { pos: start_pos })
+        };
+        Ok(TokenWithPos { token: Token::Number { value }, start_pos, end_pos: self.position.clone()})
     }
 
     fn make_string(&mut self) -> Result<TokenWithPos, LexerError> {
@@ -193,10 +215,11 @@ impl Lexer {
         Ok(TokenWithPos { token: Token::Identifier { value }, start_pos, end_pos: self.position.clone() })
     }
 
-    pub fn run(&mut self) -> Result<Vec<TokenWithPos>, LexerError> {
+    pub fn run(&mut self) -> Result<Vec<TokenWithPos>, Vec<LexerError>> {
         self.advance();
 
         let mut result: Vec<TokenWithPos> = vec![];
+        let mut errors: Vec<LexerError> = vec![];
         let mut comment = 0;
         let mut is_comment = false;
 
@@ -243,14 +266,18 @@ impl Lexer {
                     self.advance();
                 }
                 '-' => {
-                    let token = match self.make_number() {
-                        Ok(res) => res,
-                        Err(_) => {
-                            self.advance();
-                            self.token(Token::Minus)
+                    // A minus directly followed by a digit starts a negative number, unless it follows a value like in `a -1`
+                    let follows_value = matches!(result.last().map(|token| &token.token), Some(Token::Number { .. } | Token::Identifier { .. } | Token::Text { .. } | Token::String { .. } | Token::Variable { .. } | Token::CloseParen));
+                    if !follows_value && self.next_char().is_some_and(|next| next.is_ascii_digit() || next == '.') {
+                        let token = self.make_number();
+                        if token.is_err() {
+                            self.skip_number();
                         }
-                    };
-                    result.push(token);
+                        collect(token, &mut result, &mut errors);
+                    } else {
+                        result.push(self.token(Token::Minus));
+                        self.advance();
+                    }
                 }
                 '*' => {
                     result.push(self.token(Token::Multiply));
@@ -274,10 +301,7 @@ impl Lexer {
                     result.push(self.token(Token::Colon));
                     self.advance();
                 }
-                '!' => {
-                    result.push(self.token(Token::ExclamationMark));
-                    self.advance();
-                }
+                '!' => result.push(self.operator(Token::ExclamationMark, Token::NotEqual)),
                 '.' => {
                     result.push(self.token(Token::Dot));
                     self.advance();
@@ -286,9 +310,15 @@ impl Lexer {
                     result.push(self.token(Token::Comma));
                     self.advance();
                 }
-                '=' => {
-                    result.push(self.token(Token::Equal));
+                '=' => result.push(self.operator(Token::Equal, Token::EqualEqual)),
+                '<' => result.push(self.operator(Token::Less, Token::LessEqual)),
+                '>' => result.push(self.operator(Token::Greater, Token::GreaterEqual)),
+                '&' | '|' if self.next_char() == Some(current) => {
+                    let start_pos = self.position.clone();
+                    let token = if current == '&' { Token::And } else { Token::Or };
+                    self.advance();
                     self.advance();
+                    result.push(TokenWithPos::new(token, start_pos, self.position.clone()));
                 }
                 ';' => {
                     result.push(self.token(Token::Semicolon));
@@ -302,23 +332,63 @@ impl Lexer {
                     result.push(self.token(Token::Dollar));
                     self.advance();
                 }
-                '0'..='9' => result.push(self.make_number()?),
-                '\'' => result.push(self.make_string()?),
-                '"' => result.push(self.make_text()?),
-                '`' => result.push(self.make_variable()?),
-                'a'..='z' => result.push(self.make_identifier_or_keyword()?),
-                'A'..='Z' => result.push(self.make_identifier_or_keyword()?),
-                '_' => result.push(self.make_identifier_or_keyword()?),
+                '0'..='9' => {
+                    let token = self.make_number();
+                    if token.is_err() {
+                        self.skip_number();
+                    }
+                    collect(token, &mut result, &mut errors);
+                }
+                '\'' => collect(self.make_string(), &mut result, &mut errors),
+                '"' => collect(self.make_text(), &mut result, &mut errors),
+                '`' => collect(self.make_variable(), &mut result, &mut errors),
+                'a'..='z' => collect(self.make_identifier_or_keyword(), &mut result, &mut errors),
+                'A'..='Z' => collect(self.make_identifier_or_keyword(), &mut result, &mut errors),
+                '_' => collect(self.make_identifier_or_keyword(), &mut result, &mut errors),
                 _ => {
-                    return Err(LexerError::InvalidToken { token: current, pos: self.position.clone() });
+                    errors.push(LexerError::InvalidToken { token: current, pos: self.position.clone() });
+                    self.advance();
                 }
             }
         }
 
+        if !errors.is_empty() {
+            return Err(errors);
+        }
         Ok(result)
     }
 
+    fn next_char(&self) -> Option<char> {
+        self.input.chars().nth((self.char_pos + 1) as usize)
+    }
+
+    fn skip_number(&mut self) {
+        while self.current_char.is_some() && (self.current_char.unwrap().is_ascii_digit() || self.current_char.unwrap() == '.') {
+            self.advance();
+        }
+    }
+
+    /// Makes `with_equal` if the current char is followed by `=`, like `<=`
+    fn operator(&mut self, single: Token, with_equal: Token) -> TokenWithPos {
+        if self.next_char() != Some('=') {
+            let token = self.token(single);
+            self.advance();
+            return token
+        }
+        let start_pos = self.position.clone();
+        self.advance();
+        self.advance();
+        TokenWithPos::new(with_equal, start_pos, self.position.clone())
+    }
+
     fn token(&self, token: Token) -> TokenWithPos {
         TokenWithPos::new(token, self.position.clone(), self.position.clone())
     }
+}
+
+fn collect(token: Result<TokenWithPos, LexerError>, result: &mut Vec<TokenWithPos>, errors: &mut Vec<LexerError>) {
+    match token {
+        Ok(token) => result.push(token),
+        Err(err) => errors.push(err)
+    }
 }
\ No newline at end of file
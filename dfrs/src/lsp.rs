@@ -4,11 +4,12 @@ use dashmap::DashMap;
 use crate::compile::compile;
 use crate::definitions::action_dump::{ActionDump, RawActionDump};
 use crate::definitions::game_values::GameValues;
-use crate::lexer::{Lexer, LexerError};
+use crate::diagnostic::{self, Severity};
+use crate::lexer::Lexer;
 use crate::load_config;
-use crate::parser::{ParseError, Parser};
+use crate::parser::Parser;
 use crate::token::{Keyword, Token};
-use crate::validate::{ValidateError, Validator};
+use crate::validate::{ProjectProcesses, Validator};
 use ropey::Rope;
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer, LspService, Server};
@@ -18,6 +19,8 @@ use crate::definitions::events::{EntityEvents, PlayerEvents};
 struct Backend {
     client: Client,
     document_map: DashMap<String, Rope>,
+    /// Processes of the files in each project directory, updated whenever a file gets checked
+    projects: DashMap<PathBuf, ProjectProcesses>,
 
     player_events: PlayerEvents,
     entity_events: EntityEvents,
@@ -98,23 +101,15 @@ impl LanguageServer for Backend {
         let uri = params.text_document.uri.clone();
         let rope = self.document_map.get(&uri.to_string()).unwrap();
         let path = params.text_document.uri.to_file_path().unwrap();
+        let dir = path.parent().map(|dir| dir.to_path_buf()).unwrap_or_default();
+        let mut project = self.projects.entry(dir.clone()).or_insert_with(|| ProjectProcesses::scan(&dir));
 
-        match compile_file(rope.to_string(), path) {
+        match compile_file(rope.to_string(), path, &mut project) {
             Ok(_) => {},
-            Err(err) => {
-                let mut end_pos = err.pos.clone();
-                if err.end_pos.is_some() {
-                    end_pos = err.end_pos.unwrap();
+            Err(errors) => {
+                for err in errors {
+                    result.push(to_lsp_diagnostic(err, &uri, &rope));
                 }
-                result.push(Diagnostic {
-                    severity: Some(DiagnosticSeverity::ERROR),
-                    message: err.msg,
-                    range: Range {
-                        start: Position { line: err.pos.line - 1, character: err.pos.col - 1 },
-                        end: Position { line: end_pos.line - 1, character: end_pos.col - 1 }
-                    },
-                    ..Default::default()
-                });
             }
         }
 
@@ -293,7 +288,11 @@ impl Backend {
 
                     for game_value in game_values {
                         if game_value.dfrs_name.starts_with(&previous) || game_value.df_name.starts_with(&previous) {
-                            result.push(CompletionItem::new_simple(game_value.dfrs_name.clone(), game_value.df_name.clone()));
+                            let mut item = CompletionItem::new_simple(game_value.dfrs_name.clone(), format!("{} ({})", game_value.df_name, game_value.category));
+                            // keeps the values of a category together, in the order DF shows the categories
+                            let category = self.game_values.categories().iter().position(|category| *category == game_value.category).unwrap_or(usize::MAX);
+                            item.sort_text = Some(format!("{category:04}{}", game_value.dfrs_name));
+                            result.push(item);
                         }
                     }
                     return Ok(Some(CompletionResponse::Array(result)))
@@ -315,6 +314,7 @@ pub async fn run_lsp() {
     let (service, socket) = LspService::new(|client| Backend {
         client,
         document_map: DashMap::new(),
+        projects: DashMap::new(),
 
         player_events: PlayerEvents::new(&ad),
         entity_events: EntityEvents::new(&ad),
@@ -326,151 +326,96 @@ pub async fn run_lsp() {
     Server::new(stdin, stdout, socket).serve(service).await;
 }
 
-struct CompileErr {
-    pub pos: crate::token::Position,
-    pub end_pos: Option<crate::token::Position>,
-    pub msg: String
+fn to_lsp_range(span: &diagnostic::Span) -> Range {
+    Range {
+        start: Position { line: span.start.line.saturating_sub(1), character: span.start.col.saturating_sub(1) },
+        end: Position { line: span.end.line.saturating_sub(1), character: span.end.col.saturating_sub(1) }
+    }
 }
 
-impl CompileErr {
-    pub fn new(pos: crate::token::Position, end_pos: Option<crate::token::Position>, msg: String) -> CompileErr {
-        CompileErr { pos, end_pos, msg }
+fn to_lsp_diagnostic(err: diagnostic::Diagnostic, uri: &Url, rope: &Rope) -> Diagnostic {
+    let range = match &err.span {
+        Some(span) => to_lsp_range(span),
+        None => {
+            let last_line = rope.len_lines().saturating_sub(1);
+            let end = Position { line: last_line as u32, character: rope.line(last_line).len_chars() as u32 };
+            Range { start: end, end }
+        }
+    };
+    let related_information = err.labels.iter().map(|label| DiagnosticRelatedInformation {
+        location: Location { uri: uri.clone(), range: to_lsp_range(&label.span) },
+        message: label.message.clone()
+    }).collect::<Vec<DiagnosticRelatedInformation>>();
+    let mut message = err.message;
+    if let Some(help) = err.help {
+        message.push_str(&format!("\nhelp: {help}"));
     }
+
+    Diagnostic {
+        severity: Some(match err.severity {
+            Severity::Error => DiagnosticSeverity::ERROR,
+            Severity::Warning => DiagnosticSeverity::WARNING
+        }),
+        code: Some(NumberOrString::String(err.code.to_owned())),
+        source: Some("dfrs".to_owned()),
+        message,
+        range,
+        related_information: if related_information.is_empty() { None } else { Some(related_information) },
+        ..Default::default()
+    }
+}
+
+fn into_diagnostics<T: Into<diagnostic::Diagnostic>>(errors: Vec<T>) -> Vec<diagnostic::Diagnostic> {
+    errors.into_iter().map(|err| err.into()).collect()
 }
 
-fn compile_file(data: String, path: PathBuf) -> Result<(), CompileErr> {
+fn compile_file(data: String, path: PathBuf, project: &mut ProjectProcesses) -> Result<(), Vec<diagnostic::Diagnostic>> {
     let mut config_path = path.clone();
     config_path.set_file_name("dfrs.toml");
     let config = match load_config(&config_path) {
         Ok(res) => res,
-        Err(_) => return Err(CompileErr::new(crate::token::Position::new(0, 0), None, "No config file found".into()))
+        Err(_) => return Err(vec![
+            diagnostic::Diagnostic::error("E0301", "No config file found".into())
+                .with_span(crate::token::Position::new(1, 1), crate::token::Position::new(1, 1))
+                .with_help("use 'dfrs init <path>' to create a new config file".into())
+        ])
     };
 
     let mut lexer = Lexer::new(data.clone());
-    let result = lexer.run();
-
-    let res = match result {
+    let res = match lexer.run() {
         Ok(res) => res,
-        Err(err) => {
-            return match err {
-                LexerError::InvalidNumber { pos } => {
-                    Err(CompileErr::new(pos, None, "Invalid number".to_owned()))
-                }
-                LexerError::InvalidToken { token, pos } => {
-                    Err(CompileErr::new(pos, None, format!("Invalid token '{token}'")))
-                }
-                LexerError::UnterminatedString { pos } => {
-                    Err(CompileErr::new(pos, None, "Unterminated string".to_owned()))
-                }
-                LexerError::UnterminatedText { pos } => {
-                    Err(CompileErr::new(pos, None, "Unterminated text".to_owned()))
-                }
-                LexerError::UnterminatedVariable { pos } => {
-                    Err(CompileErr::new(pos, None, "Unterminated variable".to_owned()))
-                },
-            }
+        Err(errors) => {
+            project.update(&path, None);
+            return Err(into_diagnostics(errors))
         }
     };
 
     let mut parser = Parser::new(res);
-    let res = parser.run();
-    let node;
-    match res {
-        Ok(res) =>node = res,
-        Err(err) => {
-            match err {
-                ParseError::InvalidToken { found,expected} => {
-                    if found.is_some() {
-                        let found = found.unwrap();
-
-                        let mut i = 0;
-                        let mut expected_string = "".to_owned();
-                        for token in expected.clone() {
-                            expected_string.push_str(&format!("'{token}'"));
-                            if i < expected.len() - 1 {
-                                expected_string.push_str(", ");
-                            }
-                            i += 1;
-                        }
-
-                        return Err(CompileErr::new(found.start_pos, Some(found.end_pos), format!("Invalid token '{}', expected: {expected_string}", found.token)))
-                    } else {
-                        // println!("Invalid EOF, expected: {expected:?}");
-                    }
-                }
-                ParseError::InvalidComplexNumber { pos, msg } => {
-                    return Err(CompileErr::new(pos, None, format!("Invalid number '{msg}'")))
-                },
-                ParseError::InvalidLocation { pos, msg } => {
-                    return Err(CompileErr::new(pos, None, format!("Invalid location '{msg}'")))
-                },
-                ParseError::InvalidVector { pos, msg } => {
-                    return Err(CompileErr::new(pos, None, format!("Invalid vector '{msg}'")))
-                },
-                ParseError::InvalidSound { pos, msg } => {
-                    return Err(CompileErr::new(pos, None, format!("Invalid sound '{msg}'")))
-                },
-                ParseError::InvalidPotion { pos, msg } => {
-                    return Err(CompileErr::new(pos, None, format!("Invalid potion '{msg}'")))
-                },
-                ParseError::InvalidParticle { pos, msg } => {
-                    return Err(CompileErr::new(pos, None, format!("Invalid particle '{msg}'")))
-                },
-                ParseError::InvalidItem { pos, msg } => {
-                    return Err(CompileErr::new(pos, None, format!("Invalid item '{msg}'")))
-                },
-                ParseError::UnknownVariable { found, start_pos, end_pos } => {
-                    return Err(CompileErr::new(start_pos, Some(end_pos), format!("Unknown variable '{}'", found)))
-                },
-                ParseError::InvalidType { found, start_pos } => {
-                    return match found {
-                        Some(found) => Err(CompileErr::new(found.start_pos, Some(found.end_pos), format!("Unknown type: {}", found.token))),
-                        None => Err(CompileErr::new(start_pos, None, "Missing type".into()))
-                    }
-                },
-                ParseError::InvalidCall { pos, msg } => {
-                    return Err(CompileErr::new(pos, None, format!("Invalid function call '{msg}'")))
-                },
-            }
-            return Ok(())
+    let node = match parser.run() {
+        Ok(res) => res,
+        Err(errors) => {
+            project.update(&path, None);
+            return Err(into_diagnostics(errors))
         }
-    }
+    };
+    project.update(&path, Some(&node));
 
-    let validated;
-    match Validator::new().validate(node) {
-        Ok(res) => validated = res,
-        Err(err)  => {
-            return match err {
-                ValidateError::UnknownEvent { node } => {
-                    Err(CompileErr::new(node.start_pos, Some(node.end_pos), format!("Unknown event '{}'", node.event)))
-                }
-                ValidateError::UnknownAction { name, start_pos, end_pos } => {
-                    Err(CompileErr::new(start_pos, Some(end_pos), format!("Unknown action '{}'", name)))
-                },
-                ValidateError::MissingArgument { start_pos, end_pos, name } => {
-                    Err(CompileErr::new(start_pos, Some(end_pos), format!("Missing argument '{}'", name)))
-                }
-                ValidateError::WrongArgumentType { args, index, name, expected_types, found_type } => {
-                    Err(CompileErr::new(args.get(index as usize).unwrap().start_pos.clone(), Some(args.get(index as usize).unwrap().end_pos.clone()), format!("Wrong argument type for '{}', expected '{:?}' but found '{:?}'", name, expected_types, found_type)))
-                }
-                ValidateError::TooManyArguments { start_pos, mut end_pos, name } => {
-                    end_pos.col += name.chars().count() as u32;
-                    Err(CompileErr::new(start_pos.clone(), Some(start_pos), format!("Too many arguments for action '{}'", name)))
-                }
-                ValidateError::InvalidTagOption { tag_name, provided, options, start_pos, end_pos } => {
-                    Err(CompileErr::new(start_pos, Some(end_pos), format!("Invalid option '{}' for tag '{}', expected one of {:?}", provided, tag_name, options)))
-                }
-                ValidateError::UnknownTag { tag_name, available, start_pos, end_pos } => {
-                    Err(CompileErr::new(start_pos, Some(end_pos), format!("Unknown tag '{}', found tags: {:?}", tag_name, available)))
-                }
-                ValidateError::UnknownGameValue { game_value, start_pos, end_pos} => {
-                    Err(CompileErr::new(start_pos, Some(end_pos), format!("Unknown game value '{}'", game_value)))
-                }
-            }
+    let mut validator = Validator::new().with_project(project, &path);
+    let result = validator.validate(node);
+    let mut diagnostics = into_diagnostics(validator.take_warnings());
+    let validated = match result {
+        Ok(res) => res,
+        Err(errors) => {
+            diagnostics.extend(into_diagnostics(errors));
+            return Err(diagnostics)
         }
-    }
+    };
 
     let compiled = compile(validated, config.debug.compile);
 
+    // warnings don't stop the file from compiling but still have to be shown
+    if !diagnostics.is_empty() {
+        return Err(diagnostics)
+    }
     Ok(())
 }
\ No newline at end of file
@@ -1,19 +1,20 @@
-use std::{cmp, fs};
-use std::path::PathBuf;
+use std::{cmp, fs, thread};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+use std::path::{Path, PathBuf};
 
 use clap::{Parser as _, Subcommand};
 use crate::config::Config;
-use crate::send::send;
-use crate::token::Position;
-use crate::compile::compile;
-use crate::lexer::{Lexer, LexerError};
-use crate::parser::{ParseError, Parser};
-use crate::validate::{Validator, ValidateError};
+use crate::build::build;
+use crate::send::{read_plot, send, SendCache, SendError, SendReport};
+use crate::diagnostic::Diagnostic;
+use crate::compile::{compile, CompiledLine};
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::validate::{ProjectProcesses, Validator};
 use lsp::run_lsp;
 
 use colored::Colorize;
-use tungstenite::{connect, Message};
-use url::Url;
 use crate::decompile::Decompiler;
 
 mod lsp;
@@ -28,6 +29,8 @@ pub mod send;
 pub mod definitions;
 pub mod utility;
 pub mod decompile;
+pub mod diagnostic;
+pub mod build;
 
 pub struct ConfigFileNotFoundError {}
 
@@ -44,45 +47,145 @@ pub fn load_config(file: &PathBuf) -> Result<Config, ConfigFileNotFoundError> {
     }
 }
 
-fn print_err(message: String, data: String, start_pos: Position, end_pos: Option<Position>) {
-    let lines = data.split("\n").collect::<Vec<&str>>();
-    let line = lines.get((start_pos.line - 1) as usize).unwrap();
-    let ln = start_pos.line;
-    let ln_length = ln.to_string().chars().count();
-
-    println!("{} {}", "Error:".bright_red(), message);
-    println!("{} {}", " ".repeat(ln_length), "|".bright_black());
-    println!("{} {} {}", ln.to_string().bright_black(), "|".bright_black(), line);
-    let arrows;
-    match end_pos {
-        Some(end_pos) => {
-            if end_pos.line != start_pos.line {
-                // TODO
-                return;
-            }
-            arrows = "^".repeat(cmp::max(end_pos.col - start_pos.col, 1) as usize).bright_blue();
+/// Exit status used when a file contains errors.
+const EXIT_FAILURE: i32 = 1;
+/// Exit status used when dfrs could not run at all, e.g. because a file or the config is missing.
+const EXIT_USAGE: i32 = 2;
+
+#[derive(Clone, Copy, PartialEq, clap::ValueEnum)]
+enum MessageFormat {
+    Human,
+    Json
+}
+
+fn print_diagnostics<T: Into<Diagnostic>>(errors: Vec<T>, file: &Path, data: &str, format: MessageFormat) {
+    print!("{}", format_diagnostics(errors, file, data, format));
+}
+
+/// Renders the diagnostics in the order they appear in the file, whether they are errors or warnings
+fn format_diagnostics<T: Into<Diagnostic>>(errors: Vec<T>, file: &Path, data: &str, format: MessageFormat) -> String {
+    let mut diagnostics: Vec<Diagnostic> = errors.into_iter().map(Into::into).collect();
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.as_ref().map(|span| (span.start.line, span.start.col)));
+    let mut output = String::new();
+    for diagnostic in diagnostics {
+        match format {
+            MessageFormat::Human => output.push_str(&diagnostic.render(data)),
+            MessageFormat::Json => output.push_str(&format!("{}\n", diagnostic.to_json(file)))
+        }
+    }
+    output
+}
+
+fn report_missing_config(file: &Path, format: MessageFormat) {
+    match format {
+        MessageFormat::Human => {
+            println!("{} No config file found", "Error:".bright_red());
+            println!("{} dfrs init <path> {}", "Use".bright_black(), "to create a new config file".bright_black());
         }
-        None => {
-            arrows = "^".bright_blue();
+        MessageFormat::Json => {
+            let diagnostic = Diagnostic::error("E0301", "No config file found".into())
+                .with_help("use 'dfrs init <path>' to create a new config file".into());
+            println!("{}", diagnostic.to_json(file));
         }
     }
-    println!("{} {} {}{}", " ".repeat(ln_length), "|".bright_black(), " ".repeat((start_pos.col - 1) as usize), arrows);
 }
 
-fn compile_cmd(file: &PathBuf) {
-    println!("{} {}", "Compiling".bright_black(), file.file_name().unwrap().to_string_lossy());
-    let mut config_file = file.clone();
+fn config_path(file: &Path) -> PathBuf {
+    let mut config_file = file.to_path_buf();
     config_file.set_file_name("dfrs.toml");
-    let config = match load_config(&config_file) {
+    config_file
+}
+
+fn cache_path(file: &Path) -> PathBuf {
+    let mut cache_file = file.to_path_buf();
+    cache_file.set_file_name(".dfrs-cache.json");
+    cache_file
+}
+
+fn compile_cmd(file: &PathBuf, format: MessageFormat, project: &ProjectProcesses, force: bool) -> Result<(), i32> {
+    if format == MessageFormat::Human {
+        println!("{} {}", "Compiling".bright_black(), file.file_name().unwrap().to_string_lossy());
+    }
+    let config = match load_config(&config_path(file)) {
         Ok(res) => res,
         Err(_) => {
-            println!("{} No config file found", "Error:".bright_red());
-            println!("{} dfrs init <path> {}", "Use".bright_black(), "to create a new config file".bright_black());
-            return;
+            report_missing_config(file, format);
+            return Err(EXIT_USAGE);
         }
     };
 
-    let data = std::fs::read_to_string(file).expect("could not open file");
+    let compiled = compile_file(file, &config, format, project)?;
+    if format == MessageFormat::Human {
+        println!("{}  {}", "Compiled".green(), file.file_name().unwrap().to_string_lossy());
+    }
+    let cache_path = cache_path(file);
+    let mut cache = SendCache::load(&cache_path);
+    if force {
+        cache.forget(&compiled);
+    }
+    let sent = report_send(send(compiled, &config, &mut cache), file, format);
+    report_cache_save(cache.save(&cache_path), &cache_path, format);
+    if sent { Ok(()) } else { Err(EXIT_FAILURE) }
+}
+
+fn report_send(result: Result<SendReport, SendError>, file: &Path, format: MessageFormat) -> bool {
+    match (result, format) {
+        (Ok(report), MessageFormat::Human) => {
+            for name in &report.skipped {
+                println!("{} {} {}", "Skipped".bright_black(), name, "(unchanged)".bright_black());
+            }
+            if report.sent > 0 {
+                println!("{}      {} templates", "Sent".green(), report.sent);
+            }
+            true
+        }
+        (Ok(_), MessageFormat::Json) => true,
+        (Err(err), _) => {
+            report_send_error(err, file, format);
+            false
+        }
+    }
+}
+
+fn report_send_error(err: SendError, file: &Path, format: MessageFormat) {
+    match format {
+        MessageFormat::Human => println!("{} {}", "Error:".bright_red(), err),
+        MessageFormat::Json => println!("{}", Diagnostic::error("E0303", err.to_string()).to_json(file))
+    }
+}
+
+fn report_cache_save(result: std::io::Result<()>, path: &Path, format: MessageFormat) {
+    if let Err(err) = result {
+        match format {
+            MessageFormat::Human => println!("{} Failed to save send cache: {}", "Warning:".yellow(), err),
+            MessageFormat::Json => println!("{}", Diagnostic::warning("W0300", format!("Failed to save send cache: {err}")).to_json(path))
+        }
+    }
+}
+
+fn check_cmd(file: &PathBuf, format: MessageFormat, project: &ProjectProcesses) -> Result<(), i32> {
+    if format == MessageFormat::Human {
+        println!("{} {}", "Checking".bright_black(), file.file_name().unwrap().to_string_lossy());
+    }
+    // Checking never sends anything, so the defaults are fine when there is no config
+    let config = load_config(&config_path(file)).unwrap_or_default();
+
+    compile_file(file, &config, format, project)?;
+    if format == MessageFormat::Human {
+        println!("{}  {}", "Checked".green(), file.file_name().unwrap().to_string_lossy());
+    }
+    Ok(())
+}
+
+/// Runs the lexer, parser, validator and compiler on a file, printing any errors.
+fn compile_file(file: &PathBuf, config: &Config, format: MessageFormat, project: &ProjectProcesses) -> Result<Vec<CompiledLine>, i32> {
+    let data = match fs::read_to_string(file) {
+        Ok(data) => data,
+        Err(err) => {
+            print_diagnostics(vec![Diagnostic::error("E0304", format!("Failed to read file: {err}"))], file, "", format);
+            return Err(EXIT_USAGE);
+        }
+    };
 
     let mut lexer = Lexer::new(data.clone());
     let result = lexer.run();
@@ -97,25 +200,9 @@ fn compile_cmd(file: &PathBuf) {
             }
             res
         }
-        Err(err) => {
-            match err {
-                LexerError::InvalidNumber { pos } => {
-                    print_err(format!("Invalid number in line {pos}"), data, pos, None);
-                }
-                LexerError::InvalidToken { token, pos } => {
-                    print_err(format!("Invalid token '{token}' in line {pos}"), data, pos, None);
-                }
-                LexerError::UnterminatedString { pos } => {
-                    print_err(format!("Unterminated string in line {pos}"), data, pos, None);
-                }
-                LexerError::UnterminatedText { pos } => {
-                    print_err(format!("Unterminated text in line {pos}"), data, pos, None);
-                }
-                LexerError::UnterminatedVariable { pos } => {
-                    print_err(format!("Unterminated variable in line {pos}"), data, pos, None);
-                }
-            }
-            std::process::exit(0);
+        Err(errors) => {
+            print_diagnostics(errors, file, &data, format);
+            return Err(EXIT_FAILURE);
         }
     };
 
@@ -147,6 +234,12 @@ fn compile_cmd(file: &PathBuf) {
                             node::Expression::Variable { node } => {
                                 println!("{:?} {:?} {:?}", node.var_type, node.dfrs_name, node.df_name)
                             },
+                            node::Expression::Assign { node } => {
+                                println!("{:?} {:?}", node.variable, node.value)
+                            },
+                            node::Expression::Group { node } => {
+                                println!("Group of {} expressions", node.expressions.len())
+                            },
                             
                         }
                     }
@@ -177,6 +270,12 @@ fn compile_cmd(file: &PathBuf) {
                             node::Expression::Variable { node } => {
                                 println!("{:?} {:?} {:?}", node.var_type, node.dfrs_name, node.df_name)
                             },
+                            node::Expression::Assign { node } => {
+                                println!("{:?} {:?}", node.variable, node.value)
+                            },
+                            node::Expression::Group { node } => {
+                                println!("Group of {} expressions", node.expressions.len())
+                            },
                             
                         }
                     }
@@ -185,102 +284,206 @@ fn compile_cmd(file: &PathBuf) {
             }
             node = res;
         }
+        Err(errors) => {
+            print_diagnostics(errors, file, &data, format);
+            return Err(EXIT_FAILURE);
+        }
+    }
+
+    let mut validator = Validator::new().with_project(project, file);
+    let result = validator.validate(node);
+    let mut diagnostics: Vec<Diagnostic> = validator.take_warnings().into_iter().map(Diagnostic::from).collect();
+    let validated = match result {
+        Ok(res) => res,
+        Err(errors) => {
+            diagnostics.extend(errors.into_iter().map(Diagnostic::from));
+            print_diagnostics(diagnostics, file, &data, format);
+            return Err(EXIT_FAILURE);
+        }
+    };
+    print_diagnostics(diagnostics, file, &data, format);
+
+    Ok(compile(validated, config.debug.compile))
+}
+
+fn report_missing_file(path: &Path, format: MessageFormat) {
+    match format {
+        MessageFormat::Human => println!("{} File not found", "Error:".bright_red()),
+        MessageFormat::Json => println!("{}", Diagnostic::error("E0300", "File not found".into()).to_json(path))
+    }
+}
+
+/// The path itself if it is a file, otherwise every `.dfrs` file in the directory.
+fn dfrs_files(path: &PathBuf) -> Vec<PathBuf> {
+    if !path.is_dir() {
+        return vec![path.clone()];
+    }
+    let mut files = vec![];
+    for entry in fs::read_dir(path).unwrap() {
+        let file = entry.unwrap().path();
+        if file.is_file() && file.extension().is_some_and(|extension| extension == "dfrs") {
+            files.push(file);
+        }
+    }
+    files.sort();
+    files
+}
+
+/// The directory of the project a file or directory belongs to
+fn project_dir(path: &Path) -> PathBuf {
+    if path.is_dir() { path.to_path_buf() } else { path.parent().unwrap().to_path_buf() }
+}
+
+/// Runs a command on a single file or on every `.dfrs` file in a directory and returns the exit status.
+fn run_on_path(path: &PathBuf, format: MessageFormat, verb: &str, command: impl Fn(&PathBuf, MessageFormat, &ProjectProcesses) -> Result<(), i32>) -> i32 {
+    if !path.exists() {
+        report_missing_file(path, format);
+        return EXIT_USAGE;
+    }
+    if path.is_dir() && format == MessageFormat::Human {
+        println!("{} {}", format!("{verb} project").bright_black(), path.file_name().unwrap().to_string_lossy());
+    }
+    let project = ProjectProcesses::scan(&project_dir(path));
+    let mut status = 0;
+    for file in dfrs_files(path) {
+        if let Err(code) = command(&file, format, &project) {
+            status = cmp::max(status, code);
+        }
+    }
+    status
+}
+
+fn build_cmd(path: &PathBuf, out: &Option<PathBuf>, format: MessageFormat) -> i32 {
+    if !path.exists() {
+        report_missing_file(path, format);
+        return EXIT_USAGE;
+    }
+    let project_dir = project_dir(path);
+    let out = match out {
+        Some(out) => out.clone(),
+        None => project_dir.join("build")
+    };
+    let project = ProjectProcesses::scan(&project_dir);
+
+    let mut status = 0;
+    let mut code = vec![];
+    for file in dfrs_files(path) {
+        if format == MessageFormat::Human {
+            println!("{} {}", "Building".bright_black(), file.file_name().unwrap().to_string_lossy());
+        }
+        // Building never sends anything, so the defaults are fine when there is no config
+        let config = load_config(&config_path(&file)).unwrap_or_default();
+        match compile_file(&file, &config, format, &project) {
+            Ok(compiled) => code.extend(compiled),
+            Err(err) => status = cmp::max(status, err)
+        }
+    }
+    if status != 0 {
+        return status;
+    }
+
+    match build(code, &out) {
+        Ok(manifest) => {
+            if format == MessageFormat::Human {
+                println!("{}  {} templates to {}", "Built".green(), manifest.templates.len(), out.to_string_lossy());
+            }
+            0
+        }
         Err(err) => {
-            match err {
-                ParseError::InvalidToken { found,expected} => {
-                    if found.is_some() {
-                        let found = found.unwrap();
-
-                        let mut i = 0;
-                        let mut expected_string = "".to_owned();
-                        for token in expected.clone() {
-                            expected_string.push_str(&format!("'{token}'"));
-                            if i < expected.len() - 1 {
-                                expected_string.push_str(", ");
-                            }
-                            i += 1;
-                        }
+            match format {
+                MessageFormat::Human => println!("{} Failed to write build output: {}", "Error:".bright_red(), err),
+                MessageFormat::Json => println!("{}", Diagnostic::error("E0302", format!("Failed to write build output: {err}")).to_json(&out))
+            }
+            EXIT_FAILURE
+        }
+    }
+}
 
-                        print_err(format!("Invalid token '{}', expected: {expected_string}", found.token), data, found.start_pos, Some(found.end_pos));
-                    } else {
-                        println!("Invalid EOF, expected: {expected:?}");
-                    }
-                }
-                ParseError::InvalidCall { pos, msg } => {
-                    print_err(format!("Invalid function call: {}", msg), data, pos, None)
-                }
-                ParseError::InvalidComplexNumber { pos, msg } => {
-                    print_err(format!("Invalid Number: {}", msg), data, pos, None)
-                }
-                ParseError::InvalidLocation { pos, msg } => {
-                    print_err(format!("Invalid Location: {}", msg), data, pos, None)
-                }
-                ParseError::InvalidVector { pos, msg } => {
-                    print_err(format!("Invalid Vector: {}", msg), data, pos, None)
-                }
-                ParseError::InvalidSound { pos, msg } => {
-                    print_err(format!("Invalid Sound: {}", msg), data, pos, None)
-                }
-                ParseError::InvalidPotion { pos, msg } => {
-                    print_err(format!("Invalid Potion: {}", msg), data, pos, None)
-                }
-                ParseError::InvalidParticle { pos, msg } => {
-                    print_err(format!("Invalid Particle: {}", msg), data, pos, None)
-                }
-                ParseError::InvalidItem { pos, msg } => {
-                    print_err(format!("Invalid Item: {}", msg), data, pos, None)
-                }
-                ParseError::UnknownVariable { found, start_pos, end_pos } => {
-                    print_err(format!("Unknown variable: {}", found), data, start_pos, Some(end_pos))
-                }
-                ParseError::InvalidType { found, start_pos } => {
-                    match found {
-                        Some(found) => print_err(format!("Unknown type: {}", found.token), data, found.start_pos, Some(found.end_pos)),
-                        None => print_err("Missing type".into(), data, start_pos, None)
-                    }
-                },
+/// What `dfrs watch` remembers between rounds
+#[derive(Default)]
+struct WatchState {
+    modified: HashMap<PathBuf, SystemTime>,
+    /// The processes of the project when it was last compiled
+    project: Option<ProjectProcesses>
+}
+
+impl WatchState {
+    /// The files that have to be compiled again, every file if the processes of the project changed
+    fn changed_files(&mut self, dir: &PathBuf) -> Vec<PathBuf> {
+        let files = dfrs_files(dir);
+        let known = self.modified.len();
+        // Deleted and renamed files are forgotten, the files that start their processes have to be checked again
+        self.modified.retain(|file, _| files.contains(file));
+        let removed = self.modified.len() != known;
+
+        let mut changed = vec![];
+        for file in &files {
+            let time = match fs::metadata(file).and_then(|metadata| metadata.modified()) {
+                Ok(time) => time,
+                Err(_) => continue
+            };
+            if self.modified.insert(file.clone(), time) != Some(time) {
+                changed.push(file.clone());
             }
-            std::process::exit(0);
         }
+        if changed.is_empty() && !removed {
+            return changed;
+        }
+
+        let project = ProjectProcesses::scan(dir);
+        let processes_changed = self.project.as_ref().is_some_and(|old| *old != project);
+        self.project = Some(project);
+        if processes_changed { files } else { changed }
     }
+}
 
-    let validated;
-    match Validator::new().validate(node) {
-        Ok(res) => validated = res,
-        Err(err)  => {
-            match err {
-                ValidateError::UnknownEvent { node } => {
-                    print_err(format!("Unknown event '{}'", node.event), data, node.start_pos, Some(node.name_end_pos));
-                }
-                ValidateError::UnknownAction { name, start_pos, end_pos } => {
-                    print_err(format!("Unknown action '{}'", name), data, start_pos, Some(end_pos));
-                }
-                ValidateError::MissingArgument { name, start_pos, end_pos } => {
-                    print_err(format!("Missing argument '{}'", name), data, start_pos, Some(end_pos));
-                }
-                ValidateError::WrongArgumentType { args, index, name, expected_types, found_type } => {
-                    print_err(format!("Wrong argument type for '{}', expected '{:?}' but found '{:?}'", name, expected_types, found_type), data, args.get(index as usize).unwrap().start_pos.clone(), Some(args.get(index as usize).unwrap().end_pos.clone()));
-                }
-                ValidateError::TooManyArguments { start_pos, end_pos, name } => {
-                    print_err(format!("Too many arguments for action '{}'", name), data, start_pos, Some(end_pos));
-                }
-                ValidateError::InvalidTagOption { tag_name, provided, options, start_pos, end_pos } => {
-                    print_err(format!("Invalid option '{}' for tag '{}', expected one of {:?}", provided, tag_name, options), data, start_pos, Some(end_pos));
-                }
-                ValidateError::UnknownTag { tag_name, available, start_pos, end_pos } => {
-                    print_err(format!("Unknown tag '{}', found tags: {:?}", tag_name, available), data, start_pos, Some(end_pos));
-                }
-                ValidateError::UnknownGameValue { game_value, start_pos, end_pos} => {
-                    print_err(format!("Unknown game_value '{game_value}'"), data, start_pos, Some(end_pos));
+fn watch_cmd(path: &PathBuf) -> i32 {
+    if !path.is_dir() {
+        println!("{} Path is not a directory", "Error:".bright_red());
+        return EXIT_USAGE;
+    }
+    let config = load_config(&path.join("dfrs.toml")).unwrap_or_default();
+    let cache_path = path.join(".dfrs-cache.json");
+    let mut cache = SendCache::load(&cache_path);
+
+    let mut state = WatchState::default();
+    // Hashes of the last compiled version of each line, so only changed templates get sent
+    let mut hashes: HashMap<String, String> = HashMap::new();
+    let mut first_run = true;
+
+    println!("{} {}", "Watching".bright_black(), path.to_string_lossy());
+    loop {
+        for file in state.changed_files(path) {
+            println!("{} {}", "Compiling".bright_black(), file.file_name().unwrap().to_string_lossy());
+            let project = state.project.as_ref().expect("Project is scanned when files change");
+            let compiled = match compile_file(&file, &config, MessageFormat::Human, project) {
+                Ok(compiled) => compiled,
+                Err(_) => continue
+            };
+            println!("{}  {}", "Compiled".green(), file.file_name().unwrap().to_string_lossy());
+
+            let mut changed = vec![];
+            for line in compiled {
+                let hash = line.hash();
+                if hashes.get(&line.name) != Some(&hash) {
+                    hashes.insert(line.name.clone(), hash);
+                    changed.push(line);
                 }
             }
-            std::process::exit(0);
+            if first_run || !config.watch.send {
+                continue;
+            }
+            if changed.is_empty() {
+                println!("{}", "No templates changed".bright_black());
+            } else {
+                println!("{} {} templates", "Sending".bright_black(), changed.len());
+                report_send(send(changed, &config, &mut cache), &file, MessageFormat::Human);
+                report_cache_save(cache.save(&cache_path), &cache_path, MessageFormat::Human);
+            }
         }
+        first_run = false;
+        thread::sleep(Duration::from_millis(config.watch.interval));
     }
-
-    let compiled = compile(validated, config.debug.compile);
-    println!("{}  {}", "Compiled".green(), file.file_name().unwrap().to_string_lossy());
-    send(compiled, config);
 }
 
 #[derive(clap::Parser)]
@@ -294,6 +497,33 @@ struct Cli {
 enum Commands {
     Compile {
         path: PathBuf,
+        /// How errors are reported
+        #[arg(long, value_enum, default_value_t = MessageFormat::Human)]
+        message_format: MessageFormat,
+        /// Place every line, even if it did not change since it was last sent
+        #[arg(long)]
+        force: bool
+    },
+    /// Validate files without sending them
+    Check {
+        path: PathBuf,
+        /// How errors are reported
+        #[arg(long, value_enum, default_value_t = MessageFormat::Human)]
+        message_format: MessageFormat
+    },
+    /// Write compiled templates to disk
+    Build {
+        path: PathBuf,
+        /// Output directory, defaults to the build directory of the project
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// How errors are reported
+        #[arg(long, value_enum, default_value_t = MessageFormat::Human)]
+        message_format: MessageFormat
+    },
+    /// Recompile files whenever they change
+    Watch {
+        path: PathBuf
     },
     Init {
         path: PathBuf,
@@ -309,33 +539,35 @@ fn main() {
     let cli = Cli::parse();
 
     match &cli.command {
-        Some(Commands::Compile { path }) => {
-            if !path.exists() {
-                println!("{} File not found", "Error:".bright_red());
-                return;
+        Some(Commands::Compile { path, message_format, force }) => {
+            let status = run_on_path(path, *message_format, "Compiling", |file, format, project| compile_cmd(file, format, project, *force));
+            if status != 0 {
+                std::process::exit(status);
             }
-            if path.is_dir() {
-                let paths = fs::read_dir(path).unwrap();
-
-                println!("{} {}", "Compiling project".bright_black(), path.file_name().unwrap().to_string_lossy());
-                for path in paths {
-                    let file = path.unwrap().path();
-                    if file.is_file() && file.extension().unwrap() == "dfrs" {
-                        compile_cmd(&file);
-                    }
-                }
-            } else {
-                compile_cmd(path);
+        }
+        Some(Commands::Check { path, message_format }) => {
+            let status = run_on_path(path, *message_format, "Checking", check_cmd);
+            if status != 0 {
+                std::process::exit(status);
+            }
+        }
+        Some(Commands::Build { path, out, message_format }) => {
+            let status = build_cmd(path, out, *message_format);
+            if status != 0 {
+                std::process::exit(status);
             }
         }
+        Some(Commands::Watch { path }) => {
+            std::process::exit(watch_cmd(path));
+        }
         Some(Commands::Init { path }) => {
             if !path.exists() {
                 println!("{} File not found", "Error:".bright_red());
-                return;
+                std::process::exit(EXIT_USAGE);
             }
             if !path.is_dir() {
                 println!("{} Path is not a directory", "Error:".bright_red());
-                return;
+                std::process::exit(EXIT_USAGE);
             }
             println!("{} {}", "Initializing new project in".bright_black(), path.to_string_lossy());
             let new_config = Config::default();
@@ -349,14 +581,16 @@ fn main() {
             decompiler.decompile(code);
         }
         Some(Commands::DecompilePlot {}) => {
-            let (mut socket, response) = connect(Url::parse("ws://localhost:31375").unwrap()).expect("Can't connect");
-            socket.send(Message::Text("scopes read_plot".into())).unwrap();
-
-            let msg = socket.read().expect("Error reading message");
-            socket.send(Message::Text("scan".into())).unwrap();
-            let msg = socket.read().expect("Error reading message");
+            let config = load_config(&PathBuf::from("dfrs.toml")).unwrap_or_default();
+            let plot = match read_plot(&config) {
+                Ok(plot) => plot,
+                Err(err) => {
+                    report_send_error(err, Path::new("dfrs.toml"), MessageFormat::Human);
+                    std::process::exit(EXIT_FAILURE);
+                }
+            };
 
-            for line in msg.to_text().unwrap().split('\n') {
+            for line in plot.split('\n') {
                 let mut decompiler = Decompiler::new();
                 decompiler.decompile(line);
             }
@@ -366,4 +600,74 @@ fn main() {
         }
         None => {}
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh project directory in the temp dir containing the given files
+    fn project(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dfrs-test-{name}"));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("Failed to create project");
+        for (file, source) in files {
+            fs::write(dir.join(file), source).expect("Failed to write file");
+        }
+        dir
+    }
+
+    #[test]
+    fn diagnostics_are_ordered_by_position() {
+        let diagnostics = vec![
+            Diagnostic::warning("W0201", "later".into()).with_span(token::Position::new(5, 1), token::Position::new(5, 4)),
+            Diagnostic::error("E0202", "earlier".into()).with_span(token::Position::new(2, 3), token::Position::new(2, 6))
+        ];
+        let output = format_diagnostics(diagnostics, Path::new("main.dfrs"), "", MessageFormat::Json);
+        let codes: Vec<String> = output.lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).expect("Invalid json")["code"].as_str().unwrap().to_owned())
+            .collect();
+        assert_eq!(codes, ["E0202", "W0201"]);
+    }
+
+    #[test]
+    fn exit_status_tells_errors_from_usage_problems() {
+        let dir = project("exit-status", &[("bad.dfrs", "@join {\n  p.unknownThing();\n}")]);
+        assert_eq!(run_on_path(&dir, MessageFormat::Json, "Checking", check_cmd), EXIT_FAILURE);
+        assert_eq!(run_on_path(&dir.join("missing.dfrs"), MessageFormat::Json, "Checking", check_cmd), EXIT_USAGE);
+    }
+    #[test]
+    fn check_validates_without_a_config() {
+        let dir = project("check", &[("main.dfrs", "@join {\n  p.sendMessage(\"Hi\");\n}")]);
+        let file = dir.join("main.dfrs");
+        let processes = ProjectProcesses::scan(&dir);
+        assert_eq!(check_cmd(&file, MessageFormat::Json, &processes), Ok(()));
+        assert_eq!(compile_cmd(&file, MessageFormat::Json, &processes, false), Err(EXIT_USAGE));
+    }
+    #[test]
+    fn watch_recompiles_dependents_when_processes_change() {
+        let dir = project("watch", &[("a.dfrs", "proc worker {\n}"), ("b.dfrs", "@join {\n  start(\"worker\");\n}")]);
+        let (a, b) = (dir.join("a.dfrs"), dir.join("b.dfrs"));
+        let touch = |file: &PathBuf, source: &str, seconds: u64| {
+            fs::write(file, source).unwrap();
+            let time = SystemTime::UNIX_EPOCH + Duration::from_secs(seconds);
+            fs::File::options().write(true).open(file).unwrap().set_modified(time).unwrap();
+        };
+        let mut state = WatchState::default();
+        assert_eq!(state.changed_files(&dir), [a.clone(), b.clone()]);
+        assert!(state.changed_files(&dir).is_empty());
+
+        touch(&b, "@join {\n  start(\"worker\");\n}\n", 1);
+        assert_eq!(state.changed_files(&dir), [b.clone()]);
+
+        touch(&a, "proc worker {\n}\nproc other {\n}", 2);
+        assert_eq!(state.changed_files(&dir), [a.clone(), b.clone()]);
+
+        fs::remove_file(&a).unwrap();
+        assert_eq!(state.changed_files(&dir), [b.clone()]);
+        assert!(!state.modified.contains_key(&a));
+        let processes = state.project.as_ref().unwrap();
+        assert_eq!(compile_file(&b, &Config::default(), MessageFormat::Json, processes).map(|_| ()), Err(EXIT_FAILURE));
+        assert_eq!(compile_file(&a, &Config::default(), MessageFormat::Json, processes).map(|_| ()), Err(EXIT_USAGE));
+    }
 }
\ No newline at end of file
@@ -10,6 +10,8 @@ pub struct FileNode {
     pub events: Vec<EventNode>,
     pub functions: Vec<FunctionNode>,
     pub processes: Vec<ProcessNode>,
+    /// Game and save variables declared at the top of the file
+    pub variables: Vec<VariableNode>,
     pub start_pos: Position,
     pub end_pos: Position
 }
@@ -51,7 +53,9 @@ pub struct FunctionParamNode {
     pub param_type: Type,
     pub optional: bool,
     pub multiple: bool,
-    pub default: Option<ArgValueWithPos>
+    pub default: Option<ArgValueWithPos>,
+    pub start_pos: Position,
+    pub end_pos: Position
 }
 
 #[derive(Clone, Debug)]
@@ -68,7 +72,9 @@ pub enum Expression {
     Variable { node: VariableNode },
     Call { node: CallNode },
     Start { node: StartNode },
-    Repeat { node: RepeatNode }
+    Repeat { node: RepeatNode },
+    Assign { node: Box<AssignNode> },
+    Group { node: GroupNode }
 }
 
 #[derive(Clone, Debug)]
@@ -80,7 +86,9 @@ pub struct ActionNode {
     pub start_pos: Position,
     pub selector_start_pos: Position,
     pub selector_end_pos: Position,
-    pub end_pos: Position
+    pub end_pos: Position,
+    /// End of the closing parenthesis, errors about the arguments cover the whole call
+    pub args_end_pos: Position
 }
 
 #[derive(Clone, Debug)]
@@ -98,10 +106,44 @@ pub struct ConditionalNode {
     pub inverted: bool
 }
 
+/// A condition made of conditionals joined with `&&` and `||`
+#[derive(Clone, Debug)]
+pub enum Condition {
+    Single { node: ConditionalNode },
+    And { left: Box<Condition>, right: Box<Condition> },
+    Or { left: Box<Condition>, right: Box<Condition> }
+}
+
+impl Condition {
+    pub fn start_pos(&self) -> Position {
+        match self {
+            Condition::Single { node } => node.start_pos.clone(),
+            Condition::And { left, .. } | Condition::Or { left, .. } => left.start_pos()
+        }
+    }
+
+    pub fn end_pos(&self) -> Position {
+        match self {
+            Condition::Single { node } => node.end_pos.clone(),
+            Condition::And { right, .. } | Condition::Or { right, .. } => right.end_pos()
+        }
+    }
+}
+
+/// Several expressions a single statement is lowered to, like the flag variables of an `a || b` condition
+#[derive(Clone, Debug)]
+pub struct GroupNode {
+    pub expressions: Vec<ExpressionNode>,
+    pub start_pos: Position,
+    pub end_pos: Position
+}
+
 #[derive(Clone, Debug)]
 pub struct CallNode {
     pub name: String,
     pub args: Vec<Arg>,
+    /// Called as `name(args)` instead of `call("name", args)`, resolved against the functions in the file
+    pub direct: bool,
     pub start_pos: Position,
     pub end_pos: Position
 }
@@ -110,6 +152,8 @@ pub struct CallNode {
 pub struct StartNode {
     pub name: String,
     pub args: Vec<Arg>,
+    pub name_start_pos: Position,
+    pub name_end_pos: Position,
     pub start_pos: Position,
     pub end_pos: Position
 }
@@ -123,6 +167,46 @@ pub struct RepeatNode {
     pub expressions: Vec<ExpressionNode>
 }
 
+#[derive(Clone, Debug)]
+pub struct AssignNode {
+    pub variable: Arg,
+    pub value: MathNode,
+    /// The set variable actions the assignment is lowered to, filled in by the validator
+    pub actions: Vec<ActionNode>,
+    pub start_pos: Position,
+    pub end_pos: Position
+}
+
+#[derive(Clone, Debug)]
+pub enum MathNode {
+    Value { arg: Arg },
+    Binary { operator: MathOperator, left: Box<MathNode>, right: Box<MathNode> }
+}
+
+impl MathNode {
+    pub fn start_pos(&self) -> Position {
+        match self {
+            MathNode::Value { arg } => arg.start_pos.clone(),
+            MathNode::Binary { left, .. } => left.start_pos()
+        }
+    }
+
+    pub fn end_pos(&self) -> Position {
+        match self {
+            MathNode::Value { arg } => arg.end_pos.clone(),
+            MathNode::Binary { right, .. } => right.end_pos()
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MathOperator {
+    Add,
+    Sub,
+    Mul,
+    Div
+}
+
 #[derive(Clone, Debug)]
 pub struct Arg {
     pub value: ArgValue,
@@ -137,6 +221,8 @@ pub struct VariableNode {
     pub dfrs_name: String,
     pub df_name: String,
     pub var_type: VariableType,
+    /// Declared using `line name: type;`
+    pub value_type: Option<Type>,
     pub start_pos: Position,
     pub end_pos: Position
 }
@@ -154,6 +240,8 @@ pub enum ArgValue {
     Potion { potion: String, amplifier: f32, duration: f32 },
     Particle { particle: String, cluster: ParticleCluster, data: ParticleData },
     Item { item: String },
+    Vehicle { vehicle: String },
+    EntityType { entity_type: String },
     Tag { tag: String, value: Box<ArgValue>, definition: Option<DefinedTag>, name_end_pos: Position, value_start_pos: Position },
     Variable { name: String, scope: String },
     GameValue { df_name: Option<String>, dfrs_name: String, selector: Selector, selector_end_pos: Position },
@@ -225,4 +313,16 @@ pub enum VariableType {
     Local,
     Game,
     Save
+}
+
+impl VariableType {
+    /// The scope DF uses for variables of this type
+    pub fn scope(&self) -> &'static str {
+        match self {
+            VariableType::Line => "line",
+            VariableType::Local => "local",
+            VariableType::Game => "unsaved",
+            VariableType::Save => "saved",
+        }
+    }
 }
\ No newline at end of file
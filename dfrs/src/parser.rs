@@ -1,5 +1,6 @@
 use crate::{definitions::ArgType, node::{ActionNode, ActionType, Arg, ArgValue, ArgValueWithPos, CallNode, ConditionalNode, ConditionalType, EventNode, Expression, ExpressionNode, FileNode, FunctionNode, FunctionParamNode, ProcessNode, RepeatNode, VariableNode, VariableType}, token::{Keyword, Position, Selector, Token, TokenWithPos, SELECTORS, TYPES}};
-use crate::node::{ParticleCluster, ParticleData, StartNode};
+use crate::diagnostic::Diagnostic;
+use crate::node::{AssignNode, Condition, GroupNode, MathNode, MathOperator, ParticleCluster, ParticleData, StartNode};
 
 #[derive(Debug)]
 pub enum ParseError {
@@ -13,19 +14,92 @@ pub enum ParseError {
     InvalidPotion { pos: Position, msg: String },
     InvalidParticle { pos: Position, msg: String },
     InvalidItem { pos: Position, msg: String },
+    InvalidVehicle { pos: Position, msg: String },
+    InvalidEntityType { pos: Position, msg: String },
     InvalidType { found: Option<TokenWithPos>, start_pos: Position }
 }
 
+impl From<ParseError> for Diagnostic {
+    fn from(err: ParseError) -> Diagnostic {
+        match err {
+            ParseError::InvalidToken { found, expected } => {
+                let expected_string = expected.iter()
+                    .map(|token| format!("'{token}'"))
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                let diagnostic = match found {
+                    Some(found) => Diagnostic::error("E0101", format!("Invalid token '{}'", found.token))
+                        .with_span(found.start_pos, found.end_pos),
+                    None => Diagnostic::error("E0102", "Unexpected end of file".into())
+                };
+                if expected.is_empty() {
+                    diagnostic
+                } else {
+                    diagnostic.with_help(format!("expected one of {expected_string}"))
+                }
+            }
+            ParseError::UnknownVariable { found, start_pos, end_pos } => {
+                Diagnostic::error("E0103", format!("Unknown variable '{found}'"))
+                    .with_span(start_pos, end_pos)
+                    .with_help("variables have to be declared before they are used, e.g. 'line name;'".into())
+            }
+            ParseError::InvalidCall { pos, msg } => {
+                Diagnostic::error("E0104", format!("Invalid function call: {msg}")).with_span(pos.clone(), pos)
+            }
+            ParseError::InvalidComplexNumber { pos, msg } => {
+                Diagnostic::error("E0105", format!("Invalid number: {msg}")).with_span(pos.clone(), pos)
+            }
+            ParseError::InvalidLocation { pos, msg } => {
+                Diagnostic::error("E0106", format!("Invalid location: {msg}")).with_span(pos.clone(), pos)
+            }
+            ParseError::InvalidVector { pos, msg } => {
+                Diagnostic::error("E0107", format!("Invalid vector: {msg}")).with_span(pos.clone(), pos)
+            }
+            ParseError::InvalidSound { pos, msg } => {
+                Diagnostic::error("E0108", format!("Invalid sound: {msg}")).with_span(pos.clone(), pos)
+            }
+            ParseError::InvalidPotion { pos, msg } => {
+                Diagnostic::error("E0109", format!("Invalid potion: {msg}")).with_span(pos.clone(), pos)
+            }
+            ParseError::InvalidParticle { pos, msg } => {
+                Diagnostic::error("E0110", format!("Invalid particle: {msg}")).with_span(pos.clone(), pos)
+            }
+            ParseError::InvalidItem { pos, msg } => {
+                Diagnostic::error("E0111", format!("Invalid item: {msg}")).with_span(pos.clone(), pos)
+            }
+            ParseError::InvalidVehicle { pos, msg } => {
+                Diagnostic::error("E0113", format!("Invalid vehicle: {msg}")).with_span(pos.clone(), pos)
+            }
+            ParseError::InvalidEntityType { pos, msg } => {
+                Diagnostic::error("E0114", format!("Invalid entity type: {msg}")).with_span(pos.clone(), pos)
+            }
+            ParseError::InvalidType { found, start_pos } => {
+                let mut available = TYPES.keys().map(|key| format!("'{key}'")).collect::<Vec<String>>();
+                available.sort();
+                let available = available.join(", ");
+                match found {
+                    Some(found) => Diagnostic::error("E0112", format!("Unknown type '{}'", found.token))
+                        .with_span(found.start_pos, found.end_pos),
+                    None => Diagnostic::error("E0112", "Missing type".into())
+                        .with_span(start_pos.clone(), start_pos)
+                }.with_help(format!("available types are {available}"))
+            }
+        }
+    }
+}
+
 pub struct Parser {
     tokens: Vec<TokenWithPos>,
     token_index: i32,
     current_token: Option<TokenWithPos>,
     variables: Vec<VariableNode>,
+    errors: Vec<ParseError>,
+    flags: i32
 }
 
 impl Parser {
     pub fn new(tokens: Vec<TokenWithPos>) -> Parser {
-        Parser { tokens, token_index: -1, current_token: None, variables: vec![] }
+        Parser { tokens, token_index: -1, current_token: None, variables: vec![], errors: vec![], flags: 0 }
     }
 
     fn peak(&self) -> Option<TokenWithPos> {
@@ -65,8 +139,58 @@ impl Parser {
         Err(ParseError::InvalidToken { found: Some(token), expected: vec![required_token] })
     }
 
-    pub fn run(&mut self) -> Result<FileNode, ParseError> {
-        self.file()
+    pub fn run(&mut self) -> Result<FileNode, Vec<ParseError>> {
+        match self.file() {
+            Ok(node) => {
+                if self.errors.is_empty() {
+                    Ok(node)
+                } else {
+                    Err(std::mem::take(&mut self.errors))
+                }
+            }
+            Err(err) => {
+                self.errors.push(err);
+                Err(std::mem::take(&mut self.errors))
+            }
+        }
+    }
+
+    /// Records a recoverable error and skips to the end of the broken statement.
+    /// Hitting the end of the file is not recoverable and is returned as is.
+    fn recover(&mut self, err: ParseError) -> Result<(), ParseError> {
+        if let ParseError::InvalidToken { found: None, .. } = err {
+            return Err(err);
+        }
+        self.errors.push(err);
+        self.synchronize(true);
+        Ok(())
+    }
+
+    fn synchronize(&mut self, nested: bool) {
+        let mut depth = 0;
+        let mut token = self.current_token.clone();
+        while let Some(current) = token {
+            match current.token {
+                Token::Semicolon if depth == 0 => return,
+                Token::OpenParenCurly => depth += 1,
+                Token::CloseParenCurly => {
+                    if depth == 0 {
+                        if nested {
+                            // Closes the surrounding block, let the caller handle it
+                            self.token_index -= 1;
+                            return;
+                        }
+                    } else {
+                        depth -= 1;
+                        if depth == 0 && !matches!(self.peak().map(|token| token.token), Some(Token::Keyword { value: Keyword::Else })) {
+                            return;
+                        }
+                    }
+                }
+                _ => {}
+            }
+            token = self.advance();
+        }
     }
 
     fn file(&mut self) -> Result<FileNode, ParseError> {
@@ -74,31 +198,37 @@ impl Parser {
         let mut events: Vec<EventNode> = vec![];
         let mut functions: Vec<FunctionNode> = vec![];
         let mut processes: Vec<ProcessNode> = vec![];
+        let mut variables: Vec<VariableNode> = vec![];
         let start_pos = Position::new(1, 0);
 
         while token.is_some() {
-            match token.clone().unwrap().token {
-                Token::At => events.push(self.event()?),
+            let result = match token.clone().unwrap().token {
+                Token::At => self.event().map(|event| events.push(event)),
                 Token::Keyword { value } => {
                     match value {
                         Keyword::Function => {
-                            functions.push(self.function()?);
+                            self.function().map(|function| functions.push(function))
                         }
                         Keyword::Process => {
-                            processes.push(self.process()?);
+                            self.process().map(|process| processes.push(process))
                         }
                         Keyword::VarGame => {
-                            let node = self.variable(VariableType::Game)?;
-                            self.variables.push(node);
+                            self.variable(VariableType::Game).map(|node| variables.push(node))
                         }
                         Keyword::VarSave => {
-                            let node = self.variable(VariableType::Save)?;
-                            self.variables.push(node);
+                            self.variable(VariableType::Save).map(|node| variables.push(node))
                         }
-                        _ => return Err(ParseError::InvalidToken { found: self.current_token.clone(), expected: vec![Token::At, Token::Keyword { value: Keyword::Function }] })
+                        _ => Err(ParseError::InvalidToken { found: self.current_token.clone(), expected: vec![Token::At, Token::Keyword { value: Keyword::Function }] })
                     }
                 }
-                _ => return Err(ParseError::InvalidToken { found: self.current_token.clone(), expected: vec![Token::At, Token::Keyword { value: Keyword::Function }, Token::Keyword { value: Keyword::VarGame }, Token::Keyword { value: Keyword::VarSave }] })
+                _ => Err(ParseError::InvalidToken { found: self.current_token.clone(), expected: vec![Token::At, Token::Keyword { value: Keyword::Function }, Token::Keyword { value: Keyword::VarGame }, Token::Keyword { value: Keyword::VarSave }] })
+            };
+            if let Err(err) = result {
+                if let ParseError::InvalidToken { found: None, .. } = err {
+                    return Err(err);
+                }
+                self.errors.push(err);
+                self.synchronize(false);
             }
             token = self.advance();
             self.variables = self.variables.clone().into_iter().filter(|var| var.var_type == VariableType::Game || var.var_type == VariableType::Save).collect::<Vec<VariableNode>>();
@@ -109,7 +239,7 @@ impl Parser {
         } else {
             start_pos.clone()
         };
-        Ok(FileNode { events, functions, processes, start_pos, end_pos })
+        Ok(FileNode { events, functions, processes, variables, start_pos, end_pos })
     }
 
     fn event(&mut self) -> Result<EventNode, ParseError> {
@@ -138,7 +268,10 @@ impl Parser {
             token = self.advance_err()?;
             match token.token {
                 Token::CloseParenCurly => break,
-                _ => expressions.push(self.expression()?)
+                _ => match self.expression() {
+                    Ok(expression) => expressions.push(expression),
+                    Err(err) => self.recover(err)?
+                }
             }
         }
 
@@ -176,6 +309,7 @@ impl Parser {
 
         loop {
             let token = self.advance_err()?;
+            let param_start_pos = token.start_pos.clone();
             let param_name = match token.token {
                 Token::Identifier { value } => value,
                 Token::CloseParen => break,
@@ -208,6 +342,7 @@ impl Parser {
             }
 
             let token = self.advance_err()?;
+            let param_end_pos = token.end_pos.clone();
             let param_type = match token.token {
                 Token::Identifier { value } => {
                     if TYPES.contains_key(&value.clone()) {
@@ -277,8 +412,9 @@ impl Parser {
                 dfrs_name: param_name.clone(),
                 df_name: param_name.clone(),
                 var_type: VariableType::Line,
-                start_pos: Position::new(0, 0),
-                end_pos: Position::new(0, 0),
+                value_type: Some(param_type.clone()),
+                start_pos: param_start_pos.clone(),
+                end_pos: param_end_pos.clone(),
             });
 
             params.push(FunctionParamNode {
@@ -286,7 +422,9 @@ impl Parser {
                 param_type,
                 optional,
                 multiple,
-                default
+                default,
+                start_pos: param_start_pos,
+                end_pos: param_end_pos
             });
 
             let token = self.advance_err()?;
@@ -304,7 +442,10 @@ impl Parser {
             token = self.advance_err()?;
             match token.token {
                 Token::CloseParenCurly => break,
-                _ => expressions.push(self.expression()?)
+                _ => match self.expression() {
+                    Ok(expression) => expressions.push(expression),
+                    Err(err) => self.recover(err)?
+                }
             }
         }
 
@@ -328,7 +469,10 @@ impl Parser {
             token = self.advance_err()?;
             match token.token {
                 Token::CloseParenCurly => break,
-                _ => expressions.push(self.expression()?)
+                _ => match self.expression() {
+                    Ok(expression) => expressions.push(expression),
+                    Err(err) => self.recover(err)?
+                }
             }
         }
 
@@ -374,25 +518,15 @@ impl Parser {
                         end_pos = res.end_pos.clone();
                         node = Expression::Action { node: res };
                     }
-                    Keyword::IfP => {
-                        let res = self.conditional(ConditionalType::Player)?;
-                        end_pos = res.end_pos.clone();
-                        node = Expression::Conditional { node: res };
+                    Keyword::IfP | Keyword::IfE | Keyword::IfG | Keyword::IfV => {
+                        // the keyword is part of the condition
+                        self.token_index -= 1;
+                        node = self.conditional()?;
+                        end_pos = self.current_token.clone().unwrap().end_pos;
                     }
-                    Keyword::IfE => {
-                        let res = self.conditional(ConditionalType::Entity)?;
-                        end_pos = res.end_pos.clone();
-                        node = Expression::Conditional { node: res };
-                    }
-                    Keyword::IfG => {
-                        let res = self.conditional(ConditionalType::Game)?;
-                        end_pos = res.end_pos.clone();
-                        node = Expression::Conditional { node: res };
-                    }
-                    Keyword::IfV => {
-                        let res = self.conditional(ConditionalType::Variable)?;
-                        end_pos = res.end_pos.clone();
-                        node = Expression::Conditional { node: res };
+                    Keyword::If => {
+                        node = self.conditional()?;
+                        end_pos = self.current_token.clone().unwrap().end_pos;
                     }
                     Keyword::VarLine => {
                         let res = self.variable(VariableType::Line)?;
@@ -415,13 +549,22 @@ impl Parser {
                         node = Expression::Start { node: res }
                     }
                     Keyword::Repeat => {
-                        let res = self.repeat()?;
-                        end_pos = res.end_pos.clone();
-                        node = Expression::Repeat { node: res }
+                        node = self.repeat()?;
+                        end_pos = self.current_token.clone().unwrap().end_pos;
                     }
                     _ => return Err(ParseError::InvalidToken { found: self.current_token.clone(), expected: vec![Token::Keyword { value: Keyword::E }, Token::Keyword { value: Keyword::P }] })
                 }
             }
+            Token::Identifier { .. } if matches!(self.peak().map(|token| token.token), Some(Token::OpenParen)) => {
+                let res = self.direct_call()?;
+                end_pos = res.end_pos.clone();
+                node = Expression::Call { node: res }
+            }
+            Token::Identifier { .. } => {
+                let res = self.assignment()?;
+                end_pos = res.end_pos.clone();
+                node = Expression::Assign { node: Box::new(res) }
+            }
             _ => return Err(ParseError::InvalidToken { found: self.current_token.clone(), expected: vec![Token::Keyword { value: Keyword::E }, Token::Keyword { value: Keyword::P }] })
         }
 
@@ -461,6 +604,7 @@ impl Parser {
         };
 
         let args = self.make_args()?;
+        let args_end_pos = self.current_token.clone().unwrap().end_pos;
 
         let mut selector_start_pos = start_pos.clone();
         selector_start_pos.col += 2;
@@ -475,10 +619,10 @@ impl Parser {
 
         self.require_token(Token::Semicolon)?;
 
-        Ok(ActionNode { action_type, selector, name, args, start_pos, selector_start_pos, selector_end_pos, end_pos: token.end_pos })
+        Ok(ActionNode { action_type, selector, name, args, start_pos, selector_start_pos, selector_end_pos, end_pos: token.end_pos, args_end_pos })
     }
 
-    fn conditional(&mut self, conditional_type: ConditionalType) -> Result<ConditionalNode, ParseError> {
+    fn conditional_head(&mut self, conditional_type: ConditionalType) -> Result<ConditionalNode, ParseError> {
         let mut token = self.advance_err()?;
         let mut selector = Selector::Default;
         let start_pos = token.start_pos.clone();
@@ -512,61 +656,206 @@ impl Parser {
         let args = self.make_args()?;
         let end_pos = token.end_pos;
 
+        Ok(ConditionalNode {
+            conditional_type,
+            selector,
+            name,
+            args,
+            selector_start_pos,
+            selector_end_pos,
+            start_pos,
+            end_pos,
+            expressions: vec![],
+            else_expressions: vec![],
+            inverted
+        })
+    }
+
+    /// Parses `a == b`, lowering it to the matching IF VARIABLE conditional
+    fn comparison_head(&mut self) -> Result<ConditionalNode, ParseError> {
+        let mut left = self.operand()?;
+        let start_pos = left.start_pos.clone();
+        let token = self.advance_err()?;
+        let name = match token.token {
+            Token::EqualEqual => "equal",
+            Token::NotEqual => "notEqual",
+            Token::Less => "less",
+            Token::LessEqual => "lessEqual",
+            Token::Greater => "greater",
+            Token::GreaterEqual => "greaterEqual",
+            _ => return Err(ParseError::InvalidToken { found: Some(token), expected: vec![Token::EqualEqual, Token::NotEqual, Token::Less, Token::LessEqual, Token::Greater, Token::GreaterEqual] })
+        };
+        let mut right = self.operand()?;
+        let end_pos = right.end_pos.clone();
+        left.index = 0;
+        right.index = 1;
+
+        Ok(ConditionalNode {
+            conditional_type: ConditionalType::Variable,
+            selector: Selector::Default,
+            name: name.into(),
+            args: vec![left, right],
+            selector_start_pos: None,
+            selector_end_pos: None,
+            start_pos,
+            end_pos,
+            expressions: vec![],
+            else_expressions: vec![],
+            inverted: false
+        })
+    }
+
+    /// Parses a conditional statement, the next token has to start its condition
+    fn conditional(&mut self) -> Result<Expression, ParseError> {
+        let condition = self.condition()?;
+        let (expressions, else_expressions) = self.conditional_body()?;
+
+        if let Condition::Single { mut node } = condition {
+            node.expressions = expressions;
+            node.else_expressions = else_expressions;
+            return Ok(Expression::Conditional { node })
+        }
+
+        let start_pos = condition.start_pos();
+        let end_pos = condition.end_pos();
+        let expressions = if else_expressions.is_empty() {
+            self.gate(condition, expressions)
+        } else {
+            let flag = self.new_flag(&start_pos, &end_pos);
+            let mut lowered = vec![set_flag(&flag, 0.0)];
+            lowered.append(&mut self.set_if(condition, &flag));
+            let mut check = flag_check(&flag);
+            check.expressions = expressions;
+            check.else_expressions = else_expressions;
+            lowered.push(ExpressionNode { node: Expression::Conditional { node: check }, start_pos: start_pos.clone(), end_pos: end_pos.clone() });
+            lowered
+        };
+        Ok(Expression::Group { node: GroupNode { expressions, start_pos, end_pos } })
+    }
+
+    fn condition(&mut self) -> Result<Condition, ParseError> {
+        let mut condition = self.condition_and()?;
+        while let Some(Token::Or) = self.peak().map(|token| token.token) {
+            self.advance();
+            let right = self.condition_and()?;
+            condition = Condition::Or { left: Box::new(condition), right: Box::new(right) };
+        }
+        Ok(condition)
+    }
+
+    fn condition_and(&mut self) -> Result<Condition, ParseError> {
+        let mut condition = self.condition_primary()?;
+        while let Some(Token::And) = self.peak().map(|token| token.token) {
+            self.advance();
+            let right = self.condition_primary()?;
+            condition = Condition::And { left: Box::new(condition), right: Box::new(right) };
+        }
+        Ok(condition)
+    }
+
+    fn condition_primary(&mut self) -> Result<Condition, ParseError> {
+        let conditional_type = match self.peak().map(|token| token.token) {
+            Some(Token::OpenParen) => {
+                self.advance();
+                let condition = self.condition()?;
+                self.require_token(Token::CloseParen)?;
+                return Ok(condition)
+            }
+            Some(Token::Keyword { value: Keyword::IfP }) => ConditionalType::Player,
+            Some(Token::Keyword { value: Keyword::IfE }) => ConditionalType::Entity,
+            Some(Token::Keyword { value: Keyword::IfG }) => ConditionalType::Game,
+            Some(Token::Keyword { value: Keyword::IfV }) => ConditionalType::Variable,
+            _ => return Ok(Condition::Single { node: self.comparison_head()? })
+        };
+        self.advance();
+        Ok(Condition::Single { node: self.conditional_head(conditional_type)? })
+    }
+
+    /// Lowers a condition to expressions that only run `body` if it is true
+    fn gate(&mut self, condition: Condition, body: Vec<ExpressionNode>) -> Vec<ExpressionNode> {
+        match condition {
+            Condition::Single { mut node } => {
+                let (start_pos, end_pos) = (node.start_pos.clone(), node.end_pos.clone());
+                node.expressions = body;
+                vec![ExpressionNode { node: Expression::Conditional { node }, start_pos, end_pos }]
+            }
+            Condition::And { left, right } => {
+                let body = self.gate(*right, body);
+                self.gate(*left, body)
+            }
+            condition => {
+                let (start_pos, end_pos) = (condition.start_pos(), condition.end_pos());
+                let flag = self.new_flag(&start_pos, &end_pos);
+                let mut expressions = vec![set_flag(&flag, 0.0)];
+                expressions.append(&mut self.set_if(condition, &flag));
+                let mut check = flag_check(&flag);
+                check.expressions = body;
+                expressions.push(ExpressionNode { node: Expression::Conditional { node: check }, start_pos, end_pos });
+                expressions
+            }
+        }
+    }
+
+    /// Lowers a condition to expressions that set `flag` to 1 if it is true, the flag has to be set to 0 before
+    fn set_if(&mut self, condition: Condition, flag: &Arg) -> Vec<ExpressionNode> {
+        match condition {
+            Condition::Or { left, right } => {
+                let mut expressions = self.set_if(*left, flag);
+                expressions.append(&mut self.set_if(*right, flag));
+                expressions
+            }
+            condition => self.gate(condition, vec![set_flag(flag, 1.0)])
+        }
+    }
+
+    fn new_flag(&mut self, start_pos: &Position, end_pos: &Position) -> Arg {
+        self.flags += 1;
+        Arg {
+            value: ArgValue::Variable { name: format!("__dfrs_flag{}", self.flags), scope: "line".into() },
+            index: 0,
+            arg_type: ArgType::VARIABLE,
+            start_pos: start_pos.clone(),
+            end_pos: end_pos.clone()
+        }
+    }
+
+    /// Parses the block of a conditional and the optional else block after it
+    fn conditional_body(&mut self) -> Result<(Vec<ExpressionNode>, Vec<ExpressionNode>), ParseError> {
         self.require_token(Token::OpenParenCurly)?;
         let mut expressions = vec![];
         loop {
-            token = self.advance_err()?;
+            let token = self.advance_err()?;
             match token.token {
                 Token::CloseParenCurly => break,
-                _ => {
-                    let expression = self.expression()?;
-                    expressions.push(expression);
+                _ => match self.expression() {
+                    Ok(expression) => expressions.push(expression),
+                    Err(err) => self.recover(err)?
                 }
             }
         }
 
         let mut else_expressions = vec![];
-        match self.peak() {
-            Some(token) => {
+        if let Some(Token::Keyword { value: Keyword::Else }) = self.peak().map(|token| token.token) {
+            self.advance_err()?;
+            // `else if` is an else block only containing the next conditional
+            if let Some(Token::Keyword { value: Keyword::IfP | Keyword::IfE | Keyword::IfG | Keyword::IfV | Keyword::If }) = self.peak().map(|token| token.token) {
+                self.advance_err()?;
+                else_expressions.push(self.expression()?);
+                return Ok((expressions, else_expressions))
+            }
+            self.require_token(Token::OpenParenCurly)?;
+            loop {
+                let token = self.advance_err()?;
                 match token.token {
-                    Token::Keyword { value } => {
-                        match value {
-                            Keyword::Else => {
-                                self.advance_err()?;
-                                self.require_token(Token::OpenParenCurly)?;
-                                loop {
-                                    let token = self.advance_err()?;
-                                    match token.token {
-                                        Token::CloseParenCurly => break,
-                                        _ => {
-                                            let expression = self.expression()?;
-                                            else_expressions.push(expression);
-                                        }
-                                    }
-                                }
-                            }
-                            _ => {}
-                        }
+                    Token::CloseParenCurly => break,
+                    _ => match self.expression() {
+                        Ok(expression) => else_expressions.push(expression),
+                        Err(err) => self.recover(err)?
                     }
-                    _ => {}
                 }
             }
-            None => {}
         }
-        
-        Ok(ConditionalNode {
-            conditional_type,
-            selector,
-            name,
-            args,
-            selector_start_pos,
-            selector_end_pos,
-            start_pos,
-            end_pos,
-            expressions,
-            else_expressions,
-            inverted
-        })
+        Ok((expressions, else_expressions))
     }
 
     fn call(&mut self) -> Result<CallNode, ParseError> {
@@ -591,11 +880,30 @@ impl Parser {
         Ok(CallNode {
             name,
             args,
+            direct: false,
             start_pos,
             end_pos,
         })
     }
 
+    fn direct_call(&mut self) -> Result<CallNode, ParseError> {
+        let token = self.current_token.clone().unwrap();
+        let name = match token.token {
+            Token::Identifier { value } => value,
+            _ => return Err(ParseError::InvalidToken { found: Some(token), expected: vec![Token::Identifier { value: "<any>".into() }] })
+        };
+        let args = self.make_args()?;
+        let end_token = self.require_token(Token::Semicolon)?;
+
+        Ok(CallNode {
+            name,
+            args,
+            direct: true,
+            start_pos: token.start_pos,
+            end_pos: end_token.end_pos,
+        })
+    }
+
     fn start(&mut self) -> Result<StartNode, ParseError> {
         let start_pos = self.current_token.clone().unwrap().start_pos;
         let mut args = self.make_args()?;
@@ -604,6 +912,7 @@ impl Parser {
             return Err(ParseError::InvalidCall { pos: start_pos, msg: "Missing process name".into() })
         }
         let name_arg = args.remove(0);
+        let (name_start_pos, name_end_pos) = (name_arg.start_pos.clone(), name_arg.end_pos.clone());
         let name = match name_arg.value {
             ArgValue::Text { text } => text,
             _ => return Err(ParseError::InvalidCall { pos: start_pos, msg: "Invalid process name param type".into() })
@@ -618,12 +927,14 @@ impl Parser {
         Ok(StartNode {
             name,
             args,
+            name_start_pos,
+            name_end_pos,
             start_pos,
             end_pos,
         })
     }
 
-    fn repeat(&mut self) -> Result<RepeatNode, ParseError> {
+    fn repeat(&mut self) -> Result<Expression, ParseError> {
         let mut token = self.advance_err()?;
         let start_pos = token.start_pos.clone();
 
@@ -632,7 +943,15 @@ impl Parser {
             _ => return Err(ParseError::InvalidToken { found: Some(token), expected: vec![Token::Identifier { value: "any".into() }] })
         };
 
-        let args = self.make_args()?;
+        let mut condition = None;
+        let args = if name == "while" {
+            self.require_token(Token::OpenParen)?;
+            condition = Some(self.condition()?);
+            self.require_token(Token::CloseParen)?;
+            vec![]
+        } else {
+            self.make_args()?
+        };
         let end_pos = token.end_pos;
 
         self.require_token(Token::OpenParenCurly)?;
@@ -641,46 +960,79 @@ impl Parser {
             token = self.advance_err()?;
             match token.token {
                 Token::CloseParenCurly => break,
-                _ => {
-                    let expression = self.expression()?;
-                    expressions.push(expression);
+                _ => match self.expression() {
+                    Ok(expression) => expressions.push(expression),
+                    Err(err) => self.recover(err)?
                 }
             }
         }
         
-        Ok(RepeatNode {
+        let mut node = RepeatNode {
             name,
             args,
-            start_pos,
-            end_pos,
+            start_pos: start_pos.clone(),
+            end_pos: end_pos.clone(),
             expressions,
-        })
+        };
+
+        // Conditions joined with && or || are stored in a flag that is updated before every iteration
+        let condition = match condition {
+            Some(Condition::Single { node: conditional }) => {
+                node.args = vec![condition_arg(conditional)];
+                return Ok(Expression::Repeat { node })
+            }
+            Some(condition) => condition,
+            None => return Ok(Expression::Repeat { node })
+        };
+        let flag = self.new_flag(&condition.start_pos(), &condition.end_pos());
+        let mut update = vec![set_flag(&flag, 0.0)];
+        update.append(&mut self.set_if(condition, &flag));
+
+        let check = flag_check(&flag);
+        node.args = vec![condition_arg(check)];
+        node.expressions.append(&mut update.clone());
+
+        let mut expressions = update;
+        expressions.push(ExpressionNode { node: Expression::Repeat { node }, start_pos: start_pos.clone(), end_pos: end_pos.clone() });
+        Ok(Expression::Group { node: GroupNode { expressions, start_pos, end_pos } })
     }
 
     fn variable(&mut self, var_type: VariableType) -> Result<VariableNode, ParseError> {
         let start_pos = self.current_token.clone().unwrap().start_pos;
-        let end_pos = start_pos.clone();
         
         let token = self.advance_err()?;
+        let mut end_pos = token.end_pos.clone();
         let dfrs_name = match token.token {
             Token::Identifier { value } => value,
             _ => return Err(ParseError::InvalidToken { found: self.current_token.clone(), expected: vec![Token::Identifier { value: "any".into() }] })
         };
 
-        let token = self.advance_err()?;
+        let mut value_type = None;
+        let mut token = self.advance_err()?;
+        if token.token == Token::Colon {
+            let type_token = self.advance_err()?;
+            end_pos = type_token.end_pos.clone();
+            value_type = match type_token.token {
+                Token::Identifier { value } if TYPES.contains_key(&value) => Some(TYPES.get(&value).unwrap().to_owned()),
+                _ => return Err(ParseError::InvalidType { found: Some(type_token.clone()), start_pos: type_token.start_pos })
+            };
+            token = self.advance_err()?;
+        }
+
         match token.token {
             Token::Equal => {}
             Token::Semicolon => {
                 return {
-                    let node = VariableNode { dfrs_name: dfrs_name.clone(), df_name: dfrs_name, var_type, start_pos, end_pos };
+                    let node = VariableNode { dfrs_name: dfrs_name.clone(), df_name: dfrs_name, var_type, value_type, start_pos, end_pos };
                     self.variables.push(node.clone());
                     Ok(node)
                 }
             }
-            _ => return Err(ParseError::InvalidToken { found: self.current_token.clone(), expected: vec![Token::Equal, Token::Semicolon] })
+            _ => return Err(ParseError::InvalidToken { found: self.current_token.clone(), expected: vec![Token::Colon, Token::Equal, Token::Semicolon] })
         };
 
         let token = self.advance_err()?;
+        end_pos = token.end_pos.clone();
         let df_name = match token.token {
             Token::Variable { value } => value,
             _ => return Err(ParseError::InvalidToken { found: self.current_token.clone(), expected: vec![Token::Variable { value: "any".into() }] })
@@ -688,7 +1040,7 @@ impl Parser {
 
         self.require_token(Token::Semicolon)?;
 
-        let node = VariableNode { dfrs_name, df_name, var_type, start_pos, end_pos };
+        let node = VariableNode { dfrs_name, df_name, var_type, value_type, start_pos, end_pos };
         self.variables.push(node.clone());
         Ok(node)
     }
@@ -708,11 +1060,10 @@ impl Parser {
         let mut tag_start_pos = Position::new(0, 0);
         let mut tag_end_pos = Position::new(0, 0);
         let mut comma_pos = Position::new(0, 0);
-        let mut is_game_value = false;
 
         let expected = vec![Token::CloseParen, Token::Text { value: "<any>".into() }, Token::String { value: "<any>".into() }, Token::Number { value: 0.0 }, Token::Identifier { value: "Location".into() }];
         loop {
-            let mut token = self.advance_err()?;
+            let token = self.advance_err()?;
 
             if is_value {
                 match token.token {
@@ -794,38 +1145,6 @@ impl Parser {
                         return Err(ParseError::InvalidToken { found: Some(token), expected: vec![Token::String { value: "<any>".into() }, Token::Text { value: "<any>".into() }] })
                     }
                 }
-            } else if is_game_value {
-                let mut selector = Selector::Default;
-                let mut selector_end_pos = token.start_pos.clone();
-                let start_pos = token.start_pos.clone();
-
-                if let Token::Selector { value } = token.token.clone() {
-                    selector = value;
-                    token = self.advance_err()?;
-                    if token.token != Token::Colon {
-                        return Err(ParseError::InvalidToken { found: Some(token), expected: vec![Token::Colon]})
-                    }
-                    selector_end_pos = token.end_pos;
-                    token = self.advance_err()?;
-                }
-                
-                match token.token.clone() {
-                    Token::Identifier { value } => {
-                        params.push(ArgValueWithPos {
-                            value: ArgValue::GameValue {
-                                dfrs_name: value,
-                                df_name: None,
-                                selector,
-                                selector_end_pos
-                            },
-                            start_pos,
-                            end_pos: token.end_pos.clone(),
-                        });
-                        is_value = true;
-                        is_game_value = false;
-                    }
-                    _ => return Err(ParseError::InvalidToken { found: Some(token), expected: vec![Token::Identifier {value: "<any>".into()}, Token::Selector {value: Selector::Default}] })
-                }
             } else {
                 match token.token.clone() {
                     Token::Number { value } => {
@@ -882,6 +1201,14 @@ impl Parser {
                                 params.push(self.make_item()?);
                                 is_value = true;
                             }
+                            "Vehicle" => {
+                                params.push(self.make_vehicle()?);
+                                is_value = true;
+                            }
+                            "EntityType" => {
+                                params.push(self.make_entity_type()?);
+                                is_value = true;
+                            }
                             "null" => {
                                 params.push(ArgValueWithPos {
                                     value: ArgValue::Empty,
@@ -898,7 +1225,10 @@ impl Parser {
                             }
                         }
                     }
-                    Token::Dollar => is_game_value = true,
+                    Token::Dollar => {
+                        params.push(self.game_value()?);
+                        is_value = true;
+                    }
                     Token::Keyword { value } => {
                         let arg = match value {
                             Keyword::IfP => self.conditional_arg(ConditionalType::Player)?,
@@ -925,23 +1255,7 @@ impl Parser {
         let params = self.make_params()?;
         let mut args = vec![];
         for (i, param) in params.into_iter().enumerate() {
-            let arg_type = match param.value {
-                ArgValue::Empty => ArgType::EMPTY,
-                ArgValue::Number { .. } => ArgType::NUMBER,
-                ArgValue::ComplexNumber { .. } => ArgType::NUMBER,
-                ArgValue::String { .. } => ArgType::STRING,
-                ArgValue::Text { .. } => ArgType::TEXT,
-                ArgValue::Location { .. } => ArgType::LOCATION,
-                ArgValue::Potion { .. } => ArgType::POTION,
-                ArgValue::Sound { .. } => ArgType::SOUND,
-                ArgValue::Particle { .. } => ArgType::PARTICLE,
-                ArgValue::Item { .. } => ArgType::ITEM,
-                ArgValue::Vector { .. } => ArgType::VECTOR,
-                ArgValue::Tag { ..} => ArgType::TAG,
-                ArgValue::Variable { .. } => ArgType::VARIABLE,
-                ArgValue::GameValue { .. } => ArgType::GameValue,
-                ArgValue::Condition { .. } => ArgType::CONDITION
-            };
+            let arg_type = arg_type(&param.value);
             args.push(Arg { value: param.value, index: i as i32, arg_type, start_pos: param.start_pos, end_pos: param.end_pos});
         }
         Ok(args)
@@ -1254,6 +1568,192 @@ impl Parser {
         })
     }
 
+    fn make_vehicle(&mut self) -> Result<ArgValueWithPos, ParseError> {
+        let start_pos = self.current_token.clone().unwrap().start_pos;
+        let vehicle_params = self.make_params()?;
+
+        if vehicle_params.is_empty() {
+            return Err(ParseError::InvalidVehicle { pos: self.current_token.clone().unwrap().start_pos, msg: "Not enough arguments".into() })
+        }
+        let vehicle = match &vehicle_params[0].value {
+            ArgValue::String { string } => string.clone(),
+            ArgValue::Text { text } => text.clone(),
+            _ => return Err(ParseError::InvalidVehicle { pos: self.current_token.clone().unwrap().start_pos, msg: "Invalid vehicle arg type".into() })
+        };
+        if vehicle_params.len() > 1 {
+            return Err(ParseError::InvalidVehicle { pos: self.current_token.clone().unwrap().start_pos, msg: "Too many arguments".into() })
+        }
+        let Some(vehicle) = minecraft_id(&vehicle) else {
+            return Err(ParseError::InvalidVehicle { pos: self.current_token.clone().unwrap().start_pos, msg: format!("'{vehicle}' is not a valid vehicle id") })
+        };
+        Ok(ArgValueWithPos {
+            value: ArgValue::Vehicle { vehicle },
+            start_pos,
+            end_pos: self.current_token.clone().unwrap().end_pos
+        })
+    }
+
+    fn make_entity_type(&mut self) -> Result<ArgValueWithPos, ParseError> {
+        let start_pos = self.current_token.clone().unwrap().start_pos;
+        let entity_type_params = self.make_params()?;
+
+        if entity_type_params.is_empty() {
+            return Err(ParseError::InvalidEntityType { pos: self.current_token.clone().unwrap().start_pos, msg: "Not enough arguments".into() })
+        }
+        let entity_type = match &entity_type_params[0].value {
+            ArgValue::String { string } => string.clone(),
+            ArgValue::Text { text } => text.clone(),
+            _ => return Err(ParseError::InvalidEntityType { pos: self.current_token.clone().unwrap().start_pos, msg: "Invalid entity type arg type".into() })
+        };
+        if entity_type_params.len() > 1 {
+            return Err(ParseError::InvalidEntityType { pos: self.current_token.clone().unwrap().start_pos, msg: "Too many arguments".into() })
+        }
+        let Some(entity_type) = minecraft_id(&entity_type) else {
+            return Err(ParseError::InvalidEntityType { pos: self.current_token.clone().unwrap().start_pos, msg: format!("'{entity_type}' is not a valid entity type id") })
+        };
+        Ok(ArgValueWithPos {
+            value: ArgValue::EntityType { entity_type },
+            start_pos,
+            end_pos: self.current_token.clone().unwrap().end_pos
+        })
+    }
+
+    fn game_value(&mut self) -> Result<ArgValueWithPos, ParseError> {
+        let mut token = self.advance_err()?;
+        let mut selector = Selector::Default;
+        let mut selector_end_pos = token.start_pos.clone();
+        let start_pos = token.start_pos.clone();
+
+        if let Token::Selector { value } = token.token.clone() {
+            selector = value;
+            token = self.advance_err()?;
+            if token.token != Token::Colon {
+                return Err(ParseError::InvalidToken { found: Some(token), expected: vec![Token::Colon]})
+            }
+            selector_end_pos = token.end_pos;
+            token = self.advance_err()?;
+        }
+
+        match token.token.clone() {
+            Token::Identifier { value } => {
+                Ok(ArgValueWithPos {
+                    value: ArgValue::GameValue {
+                        dfrs_name: value,
+                        df_name: None,
+                        selector,
+                        selector_end_pos
+                    },
+                    start_pos,
+                    end_pos: token.end_pos.clone(),
+                })
+            }
+            _ => Err(ParseError::InvalidToken { found: Some(token), expected: vec![Token::Identifier {value: "<any>".into()}, Token::Selector {value: Selector::Default}] })
+        }
+    }
+
+    /// A single value on either side of an operator
+    fn operand(&mut self) -> Result<Arg, ParseError> {
+        let token = self.advance_err()?;
+        let value = match token.token.clone() {
+            Token::Number { value } => ArgValueWithPos { value: ArgValue::Number { number: value }, start_pos: token.start_pos, end_pos: token.end_pos },
+            Token::Text { value } => ArgValueWithPos { value: ArgValue::Text { text: value }, start_pos: token.start_pos, end_pos: token.end_pos },
+            Token::String { value } => ArgValueWithPos { value: ArgValue::String { string: value }, start_pos: token.start_pos, end_pos: token.end_pos },
+            Token::Dollar => self.game_value()?,
+            Token::Identifier { value } => {
+                match value.as_str() {
+                    "Number" => self.make_complex_number()?,
+                    "Location" => self.make_location()?,
+                    "Vector" => self.make_vector()?,
+                    "Sound" => self.make_sound()?,
+                    "Potion" => self.make_potion()?,
+                    "Particle" => self.make_particle()?,
+                    "Item" => self.make_item()?,
+                    _ => match self.get_variable(value.clone()) {
+                        Some((name, scope)) => ArgValueWithPos { value: ArgValue::Variable { name, scope }, start_pos: token.start_pos, end_pos: token.end_pos },
+                        None => return Err(ParseError::UnknownVariable { found: value, start_pos: token.start_pos, end_pos: token.end_pos })
+                    }
+                }
+            }
+            _ => return Err(ParseError::InvalidToken { found: Some(token), expected: vec![Token::Number { value: 0.0 }, Token::Text { value: "<any>".into() }, Token::String { value: "<any>".into() }, Token::Identifier { value: "<any>".into() }] })
+        };
+        Ok(Arg { arg_type: arg_type(&value.value), value: value.value, index: 0, start_pos: value.start_pos, end_pos: value.end_pos })
+    }
+
+    fn assignment(&mut self) -> Result<AssignNode, ParseError> {
+        let token = self.current_token.clone().unwrap();
+        let name = match token.token.clone() {
+            Token::Identifier { value } => value,
+            _ => return Err(ParseError::InvalidToken { found: Some(token), expected: vec![Token::Identifier { value: "<any>".into() }] })
+        };
+        let variable = match self.get_variable(name.clone()) {
+            Some((name, scope)) => Arg {
+                value: ArgValue::Variable { name, scope },
+                index: 0,
+                arg_type: ArgType::VARIABLE,
+                start_pos: token.start_pos.clone(),
+                end_pos: token.end_pos.clone()
+            },
+            None => return Err(ParseError::UnknownVariable { found: name, start_pos: token.start_pos, end_pos: token.end_pos })
+        };
+
+        self.require_token(Token::Equal)?;
+        let value = self.math_expression()?;
+        let end_token = self.require_token(Token::Semicolon)?;
+
+        Ok(AssignNode { variable, value, actions: vec![], start_pos: token.start_pos, end_pos: end_token.end_pos })
+    }
+
+    fn math_expression(&mut self) -> Result<MathNode, ParseError> {
+        let mut node = self.math_term()?;
+        loop {
+            let operator = match self.peak().map(|token| token.token) {
+                Some(Token::Plus) => MathOperator::Add,
+                Some(Token::Minus) => MathOperator::Sub,
+                _ => return Ok(node)
+            };
+            self.advance();
+            let right = self.math_term()?;
+            node = MathNode::Binary { operator, left: Box::new(node), right: Box::new(right) };
+        }
+    }
+
+    fn math_term(&mut self) -> Result<MathNode, ParseError> {
+        let mut node = self.math_factor()?;
+        loop {
+            let operator = match self.peak().map(|token| token.token) {
+                Some(Token::Multiply) => MathOperator::Mul,
+                Some(Token::Divide) => MathOperator::Div,
+                _ => return Ok(node)
+            };
+            self.advance();
+            let right = self.math_factor()?;
+            node = MathNode::Binary { operator, left: Box::new(node), right: Box::new(right) };
+        }
+    }
+
+    fn math_factor(&mut self) -> Result<MathNode, ParseError> {
+        match self.peak().map(|token| token.token) {
+            Some(Token::OpenParen) => {
+                self.advance();
+                let node = self.math_expression()?;
+                self.require_token(Token::CloseParen)?;
+                Ok(node)
+            }
+            Some(Token::Minus) => {
+                // -x is lowered as -1 * x
+                let token = self.advance_err()?;
+                let right = self.math_factor()?;
+                if let MathNode::Value { arg: Arg { value: ArgValue::Number { number }, end_pos, .. } } = right {
+                    let arg = Arg { value: ArgValue::Number { number: -number }, index: 0, arg_type: ArgType::NUMBER, start_pos: token.start_pos, end_pos };
+                    return Ok(MathNode::Value { arg })
+                }
+                let left = Arg { value: ArgValue::Number { number: -1.0 }, index: 0, arg_type: ArgType::NUMBER, start_pos: token.start_pos, end_pos: token.end_pos };
+                Ok(MathNode::Binary { operator: MathOperator::Mul, left: Box::new(MathNode::Value { arg: left }), right: Box::new(right) })
+            }
+            _ => Ok(MathNode::Value { arg: self.operand()? })
+        }
+    }
+
     fn conditional_arg(&mut self, conditional_type: ConditionalType) -> Result<ArgValueWithPos, ParseError> {
         let mut token = self.advance_err()?;
         let mut selector = Selector::Default;
@@ -1301,16 +1801,135 @@ impl Parser {
     fn get_variable(&self, value: String) -> Option<(String, String)> {
         for node in &self.variables {
             if node.dfrs_name == value {
-                let scope = match node.var_type {
-                    VariableType::Line => "line",
-                    VariableType::Local => "local",
-                    VariableType::Game => "unsaved",
-                    VariableType::Save => "saved",
-                };
-                return Some((node.df_name.clone(), scope.to_owned()))
+                return Some((node.df_name.clone(), node.var_type.scope().to_owned()))
             }
         }
 
         None
     }
+}
+
+pub fn arg_type(value: &ArgValue) -> ArgType {
+    match value {
+        ArgValue::Empty => ArgType::EMPTY,
+        ArgValue::Number { .. } => ArgType::NUMBER,
+        ArgValue::ComplexNumber { .. } => ArgType::NUMBER,
+        ArgValue::String { .. } => ArgType::STRING,
+        ArgValue::Text { .. } => ArgType::TEXT,
+        ArgValue::Location { .. } => ArgType::LOCATION,
+        ArgValue::Potion { .. } => ArgType::POTION,
+        ArgValue::Sound { .. } => ArgType::SOUND,
+        ArgValue::Particle { .. } => ArgType::PARTICLE,
+        ArgValue::Item { .. } => ArgType::ITEM,
+        ArgValue::Vehicle { .. } => ArgType::VEHICLE,
+        ArgValue::EntityType { .. } => ArgType::ENTITY_TYPE,
+        ArgValue::Vector { .. } => ArgType::VECTOR,
+        ArgValue::Tag { ..} => ArgType::TAG,
+        ArgValue::Variable { .. } => ArgType::VARIABLE,
+        ArgValue::GameValue { .. } => ArgType::GameValue,
+        ArgValue::Condition { .. } => ArgType::CONDITION
+    }
+}
+
+/// Turns names like `Oak Boat` or `minecraft:oak_boat` into `oak_boat`, or `None` if the result is not a valid id
+fn minecraft_id(name: &str) -> Option<String> {
+    let name = name.trim().to_lowercase().replace(' ', "_");
+    let id = name.strip_prefix("minecraft:").unwrap_or(&name);
+    let valid = |char: char| char.is_ascii_lowercase() || char.is_ascii_digit() || matches!(char, '_' | '.' | '-' | '/');
+    if id.is_empty() || !id.chars().all(valid) {
+        return None
+    }
+    Some(id.to_owned())
+}
+
+fn set_flag(flag: &Arg, value: f32) -> ExpressionNode {
+    let value = Arg { value: ArgValue::Number { number: value }, index: 1, arg_type: ArgType::NUMBER, start_pos: flag.start_pos.clone(), end_pos: flag.end_pos.clone() };
+    let node = ActionNode {
+        action_type: ActionType::Variable,
+        selector: Selector::Default,
+        name: "equal".into(),
+        args: vec![flag.clone(), value],
+        start_pos: flag.start_pos.clone(),
+        selector_start_pos: flag.start_pos.clone(),
+        selector_end_pos: flag.start_pos.clone(),
+        end_pos: flag.end_pos.clone(),
+        args_end_pos: flag.end_pos.clone()
+    };
+    ExpressionNode { node: Expression::Action { node }, start_pos: flag.start_pos.clone(), end_pos: flag.end_pos.clone() }
+}
+
+/// `ifv equal(flag, 1)` without a body
+fn flag_check(flag: &Arg) -> ConditionalNode {
+    let one = Arg { value: ArgValue::Number { number: 1.0 }, index: 1, arg_type: ArgType::NUMBER, start_pos: flag.start_pos.clone(), end_pos: flag.end_pos.clone() };
+    ConditionalNode {
+        conditional_type: ConditionalType::Variable,
+        selector: Selector::Default,
+        name: "equal".into(),
+        args: vec![flag.clone(), one],
+        selector_start_pos: None,
+        selector_end_pos: None,
+        start_pos: flag.start_pos.clone(),
+        end_pos: flag.end_pos.clone(),
+        expressions: vec![],
+        else_expressions: vec![],
+        inverted: false
+    }
+}
+
+/// The condition of a repeat while
+fn condition_arg(node: ConditionalNode) -> Arg {
+    Arg {
+        value: ArgValue::Condition {
+            name: node.name,
+            args: node.args,
+            selector: node.selector,
+            conditional_type: node.conditional_type,
+            inverted: node.inverted
+        },
+        index: 0,
+        arg_type: ArgType::CONDITION,
+        start_pos: node.start_pos,
+        end_pos: node.end_pos
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::Diagnostic;
+    use crate::lexer::Lexer;
+
+    /// The lines of the errors found while parsing the source
+    fn error_lines(source: &str) -> Vec<u32> {
+        let tokens = Lexer::new(source.into()).run().unwrap_or_else(|_| panic!("Failed to lex"));
+        match Parser::new(tokens).run() {
+            Ok(_) => panic!("Expected parsing to fail"),
+            Err(errors) => errors.into_iter().map(|err| Diagnostic::from(err).span.expect("Parse error without span").start.line).collect()
+        }
+    }
+
+    #[test]
+    fn collects_errors_from_every_block() {
+        let source = "@join {\n  p.sendMessage(\"a\")\n  p.sendMessage(\"b\");\n  p.;\n}\n@leave {\n  line a;\n  if a == {\n  }\n  p.sendMessage(\"c\");\n}\nfn f() {\n  e:.x();\n}";
+        assert_eq!(error_lines(source), vec![3, 4, 8, 13]);
+    }
+
+    #[test]
+    fn continues_after_the_block_an_error_was_in() {
+        let source = "@join {\n  line a;\n  if a == 1 {\n    p.;\n  } else {\n    p.;\n  }\n  p.;\n}";
+        assert_eq!(error_lines(source), vec![4, 6, 8]);
+    }
+    #[test]
+    fn vehicle_and_entity_type_names_become_minecraft_ids() {
+        let tokens = Lexer::new("@join {\n  p.a(Vehicle(\"Oak Boat\"), EntityType(\"minecraft:zombie_villager\"));\n}".into()).run().unwrap_or_else(|_| panic!("Failed to lex"));
+        let file = Parser::new(tokens).run().expect("Failed to parse");
+        let Expression::Action { node } = &file.events[0].expressions[0].node else { panic!("Expected an action") };
+        assert!(matches!(&node.args[0].value, ArgValue::Vehicle { vehicle } if vehicle == "oak_boat"));
+        assert!(matches!(&node.args[1].value, ArgValue::EntityType { entity_type } if entity_type == "zombie_villager"));
+    }
+
+    #[test]
+    fn invalid_vehicle_and_entity_type_ids_are_rejected() {
+        assert_eq!(error_lines("@join {\n  p.a(Vehicle(\"\"));\n  p.a(EntityType(\"Zombie!\"));\n  p.a(Vehicle(\"minecraft:\"));\n}"), vec![2, 3, 4]);
+    }
 }
\ No newline at end of file
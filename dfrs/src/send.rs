@@ -1,108 +1,273 @@
-use std::io::Read;
+use std::fmt::Display;
+use std::io::{ErrorKind, Read};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::thread;
+use std::time::Duration;
 use std::{io::Write, net::TcpStream};
 use base64::prelude::*;
+use colored::Colorize;
 use flate2::write::GzEncoder;
 use flate2::Compression;
 
-use crate::config::Config;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{Config, RecodeConnection};
 use crate::compile::CompiledLine;
-use tungstenite::{connect, Message};
-use url::Url;
+use tungstenite::{Message, WebSocket};
+
+/// Hashes of the lines that were last placed, stored in the project so unchanged lines don't have to be placed again
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct SendCache {
+    hashes: HashMap<String, String>
+}
+
+impl SendCache {
+    pub fn load(path: &Path) -> SendCache {
+        match fs::read_to_string(path) {
+            Ok(data) => serde_json::from_str(&data).unwrap_or_default(),
+            Err(_) => SendCache::default()
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let data = serde_json::to_string_pretty(self).expect("Failed to serialize send cache");
+        fs::write(path, data)
+    }
+
+    pub fn forget(&mut self, code: &[CompiledLine]) {
+        for line in code {
+            self.hashes.remove(&line.name);
+        }
+    }
+
+    fn is_changed(&self, line: &CompiledLine) -> bool {
+        self.hashes.get(&line.name) != Some(&line.hash())
+    }
 
-pub fn send(code: Vec<CompiledLine>, config: Config) {
+    fn update(&mut self, line: &CompiledLine) {
+        self.hashes.insert(line.name.clone(), line.hash());
+    }
+}
+
+const CONNECT_ATTEMPTS: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+pub enum SendError {
+    NotRunning { api: String, address: String },
+    AuthRefused,
+    Timeout { waiting_for: String },
+    PlacementFailed { msg: String },
+    ReadFailed { msg: String }
+}
+
+impl Display for SendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SendError::NotRunning { api, address } => {
+                write!(f, "Could not connect to {api} at {address}, make sure Minecraft is running with {api} installed")
+            }
+            SendError::AuthRefused => {
+                write!(f, "CodeClient refused to let dfrs write code, accept the request by running /auth in game")
+            }
+            SendError::Timeout { waiting_for } => {
+                write!(f, "Timed out waiting for {waiting_for}")
+            }
+            SendError::PlacementFailed { msg } => {
+                write!(f, "Failed to place templates: {msg}")
+            }
+            SendError::ReadFailed { msg } => {
+                write!(f, "Failed to read the plot: {msg}")
+            }
+        }
+    }
+}
+
+/// The outcome of a successful send
+#[derive(Debug, Default)]
+pub struct SendReport {
+    pub sent: usize,
+    /// Names of the lines that were not sent because they are unchanged
+    pub skipped: Vec<String>
+}
+
+/// Sends the lines using the configured api
+pub fn send(code: Vec<CompiledLine>, config: &Config, cache: &mut SendCache) -> Result<SendReport, SendError> {
+    let (changed, skipped): (Vec<CompiledLine>, Vec<CompiledLine>) = code.into_iter().partition(|line| cache.is_changed(line));
+    let skipped = skipped.into_iter().map(|line| line.name).collect();
+    if changed.is_empty() {
+        return Ok(SendReport { sent: 0, skipped });
+    }
     match config.sending.api {
         crate::config::SendApi::CodeClient => {
-            send_codeclient(code, config);
+            send_codeclient(&changed, config)?;
+            for line in &changed {
+                cache.update(line);
+            }
         }
         crate::config::SendApi::Recode => {
-            for line in code {
-                send_recode(line.code, line.name, config.debug.connection);
+            // Recode takes one line per connection, so the lines placed before a failure are remembered
+            for line in &changed {
+                send_recode(line, &config.sending.recode, config.debug.connection)?;
+                cache.update(line);
+            }
+        }
+    }
+    Ok(SendReport { sent: changed.len(), skipped })
+}
+
+fn connect_tcp(api: &str, address: &str, connect_timeout: u64, read_timeout: u64) -> Result<TcpStream, SendError> {
+    let not_running = || SendError::NotRunning { api: api.to_owned(), address: address.to_owned() };
+    let addresses = address.to_socket_addrs().map_err(|_| not_running())?.collect::<Vec<SocketAddr>>();
+
+    for attempt in 1..=CONNECT_ATTEMPTS {
+        for socket_address in &addresses {
+            if let Ok(stream) = TcpStream::connect_timeout(socket_address, Duration::from_millis(connect_timeout)) {
+                stream.set_read_timeout(Some(Duration::from_millis(read_timeout))).map_err(|_| not_running())?;
+                stream.set_write_timeout(Some(Duration::from_millis(read_timeout))).map_err(|_| not_running())?;
+                return Ok(stream);
             }
         }
+        if attempt < CONNECT_ATTEMPTS {
+            // Written to stderr so the notices don't end up in --message-format=json output
+            eprintln!("{} {}{} {}", "Could not connect to".bright_black(), api, ",".bright_black(), format!("retrying ({attempt}/{})", CONNECT_ATTEMPTS - 1).bright_black());
+            thread::sleep(RETRY_DELAY);
+        }
     }
+    Err(not_running())
+}
+
+fn is_timeout(err: &std::io::Error) -> bool {
+    matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut)
 }
 
-fn send_recode(code: String, name: String, debug: bool) {
-    let data = ("{\"type\": \"template\", \"source\": \"df.rs\", \"data\": \"{\\\"name\\\": \\\"".to_owned() + &name +" \\\",\\\"data\\\":\\\"" + &compress(code) + "\\\"}\"}\n").to_owned();
+fn send_recode(line: &CompiledLine, connection: &RecodeConnection, debug: bool) -> Result<(), SendError> {
+    let data = ("{\"type\": \"template\", \"source\": \"df.rs\", \"data\": \"{\\\"name\\\": \\\"".to_owned() + &line.name +" \\\",\\\"data\\\":\\\"" + &compress(line.code.clone()) + "\\\"}\"}\n").to_owned();
 
     if debug {
         println!("{}", data);
     }
 
-    let server_address = "127.0.0.1:31372";
-    match TcpStream::connect(server_address) {
-        Ok(mut stream) => {
-            if debug {
-                println!("Connected to server!");
-            }
-            match stream.write_all(data.as_bytes()) {
-                Ok(_) => {
-                    if debug {
-                        println!("Data sent successfully!");
-                        let mut buffer = [0; 2048];
-                        match stream.read(&mut buffer) {
-                            Ok(bytes_read) => {
-                                if bytes_read > 0 {
-                                    let response = String::from_utf8_lossy(&buffer[..bytes_read]);
-                                    println!("Server response: {:?}", response);
-                                } else {
-                                    println!("No data received from the server.");
-                                }
-                            }
-                            Err(err) => eprintln!("Error reading from server: {}", err),
-                        }
-                    }
-                }
-                Err(err) => eprintln!("Error sending data to server: {}", err),
-            }
-        }
-        Err(err) => eprintln!("Failed to connect to server: {}", err),
+    let address = format!("{}:{}", connection.host, connection.port);
+    let mut stream = connect_tcp("Recode", &address, connection.connect_timeout, connection.read_timeout)?;
+    if debug {
+        println!("Connected to server!");
     }
-}
+    stream.write_all(data.as_bytes()).map_err(|err| SendError::PlacementFailed { msg: err.to_string() })?;
 
-fn send_codeclient(code: Vec<CompiledLine>, config: Config) {
-    //TODO error handling
-    let (mut socket, response) = connect(Url::parse("ws://localhost:31375").unwrap()).expect("Can't connect");
-    
-    if config.debug.connection {
-        println!("Connected to server; {:?}", response)
+    let mut buffer = [0; 2048];
+    let bytes_read = match stream.read(&mut buffer) {
+        Ok(bytes_read) => bytes_read,
+        Err(err) if is_timeout(&err) => return Err(SendError::Timeout { waiting_for: "Recode to accept the template".into() }),
+        Err(err) => return Err(SendError::PlacementFailed { msg: err.to_string() })
+    };
+    if bytes_read == 0 {
+        return Err(SendError::PlacementFailed { msg: "Recode closed the connection without a reply".into() });
+    }
+    let response = String::from_utf8_lossy(&buffer[..bytes_read]);
+    if debug {
+        println!("Server response: {:?}", response);
     }
 
-    socket.send(Message::Text("scopes write_code".into())).unwrap();
+    if let Ok(response) = serde_json::from_str::<serde_json::Value>(&response) {
+        if response["status"] == "error" {
+            let msg = response["error"].as_str().unwrap_or("Recode reported an error").to_owned();
+            return Err(SendError::PlacementFailed { msg });
+        }
+    }
+    Ok(())
+}
+
+fn send_message(socket: &mut WebSocket<TcpStream>, text: String) -> Result<(), SendError> {
+    socket.send(Message::Text(text)).map_err(|err| SendError::PlacementFailed { msg: err.to_string() })
+}
 
+/// Reads the next text message, returning `None` if CodeClient ends the connection first
+fn read_text(socket: &mut WebSocket<TcpStream>, waiting_for: &str, debug: bool) -> Result<Option<String>, SendError> {
     loop {
-        let msg = socket.read().expect("Error reading message");
-        
-        if config.debug.connection {
+        let msg = match socket.read() {
+            Ok(msg) => msg,
+            Err(tungstenite::Error::Io(err)) if is_timeout(&err) => {
+                return Err(SendError::Timeout { waiting_for: waiting_for.to_owned() })
+            }
+            Err(tungstenite::Error::ConnectionClosed) | Err(tungstenite::Error::AlreadyClosed) => return Ok(None),
+            Err(err) => return Err(SendError::PlacementFailed { msg: err.to_string() })
+        };
+
+        if debug {
             println!("Received: {}", msg);
         }
 
-        if msg.to_text().expect("response should be text") == "auth" {
-            break;
+        match msg {
+            Message::Text(text) => return Ok(Some(text)),
+            Message::Close(_) => return Ok(None),
+            _ => {}
         }
     }
-    
-    socket.send(Message::Text("place swap".into())).unwrap();
-    for line in code {
-        let data = compress(line.code);
-        socket.send(Message::Text(format!("place {}", data))).unwrap();
-    }
-    socket.send(Message::Text("place go".into())).unwrap();
+}
 
+/// Reads messages until the expected one arrives, returning `closed` if CodeClient ends the connection first
+fn wait_for(socket: &mut WebSocket<TcpStream>, expected: &str, waiting_for: &str, closed: SendError, debug: bool) -> Result<(), SendError> {
     loop {
-        let msg = socket.read().expect("Error reading message");
-        
-        if config.debug.connection {
-            println!("Received: {}", msg);
+        match read_text(socket, waiting_for, debug)? {
+            Some(text) if text == expected => return Ok(()),
+            Some(_) => {}
+            None => return Err(closed)
         }
+    }
+}
 
-        if msg.to_text().expect("response should be text") == "place done" {
-            break;
-        }
+pub fn connect_codeclient(config: &Config) -> Result<WebSocket<TcpStream>, SendError> {
+    let connection = &config.sending.codeclient;
+    let address = format!("{}:{}", connection.host, connection.port);
+    let stream = connect_tcp("CodeClient", &address, connection.connect_timeout, connection.read_timeout)?;
+    let (socket, response) = tungstenite::client(format!("ws://{address}"), stream)
+        .map_err(|_| SendError::NotRunning { api: "CodeClient".into(), address })?;
+
+    if config.debug.connection {
+        println!("Connected to server; {:?}", response)
     }
+    Ok(socket)
+}
+
+fn send_codeclient(code: &[CompiledLine], config: &Config) -> Result<(), SendError> {
+    let mut socket = connect_codeclient(config)?;
+
+    send_message(&mut socket, "scopes write_code".into())?;
+    wait_for(&mut socket, "auth", "CodeClient to authorize dfrs, run /auth in game", SendError::AuthRefused, config.debug.connection)?;
+
+    send_message(&mut socket, "place swap".into())?;
+    for line in code {
+        let data = compress(line.code.clone());
+        send_message(&mut socket, format!("place {}", data))?;
+    }
+    send_message(&mut socket, "place go".into())?;
+
+    let closed = SendError::PlacementFailed { msg: "CodeClient closed the connection before all templates were placed".into() };
+    wait_for(&mut socket, "place done", "CodeClient to place the templates", closed, config.debug.connection)?;
+    let _ = socket.close(None);
+    Ok(())
+}
+
+/// Scans the plot using CodeClient and returns the templates of all lines, one per line
+pub fn read_plot(config: &Config) -> Result<String, SendError> {
+    let mut socket = connect_codeclient(config)?;
+
+    send_message(&mut socket, "scopes read_plot".into())?;
+    wait_for(&mut socket, "auth", "CodeClient to authorize dfrs, run /auth in game", SendError::AuthRefused, config.debug.connection)?;
+
+    send_message(&mut socket, "scan".into())?;
+    let plot = read_text(&mut socket, "CodeClient to scan the plot", config.debug.connection)?
+        .ok_or_else(|| SendError::ReadFailed { msg: "CodeClient closed the connection before the plot was scanned".into() })?;
+    let _ = socket.close(None);
+    Ok(plot)
 }
 
-fn compress(code: String) -> String {
+pub fn compress(code: String) -> String {
     let mut compressed_data = Vec::new();
     let mut encoder = GzEncoder::new(&mut compressed_data, Compression::default());
     
@@ -116,4 +281,129 @@ fn compress(code: String) -> String {
     }
 
     BASE64_STANDARD.encode(compressed_data)
-}   
\ No newline at end of file
+}   
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(name: &str, code: &str) -> CompiledLine {
+        CompiledLine { name: name.into(), code: code.into() }
+    }
+
+    enum Reply {
+        Nothing,
+        Text(&'static str),
+        Close
+    }
+
+    /// Config sending to a CodeClient on the given port, with a short read timeout
+    fn codeclient_config(port: u16) -> Config {
+        let mut config = Config::default();
+        config.sending.codeclient.host = "127.0.0.1".into();
+        config.sending.codeclient.port = port;
+        config.sending.codeclient.read_timeout = 500;
+        config
+    }
+
+    /// Runs a fake CodeClient that answers the messages it receives, returning the port and everything it received
+    fn fake_codeclient(reply: fn(&str) -> Reply) -> (u16, thread::JoinHandle<Vec<String>>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("Failed to bind");
+        let port = listener.local_addr().unwrap().port();
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().expect("Failed to accept");
+            let mut socket = tungstenite::accept(stream).expect("Failed to accept websocket");
+            let mut received = vec![];
+            while let Ok(Message::Text(text)) = socket.read() {
+                let reply = reply(&text);
+                received.push(text);
+                match reply {
+                    Reply::Nothing => {}
+                    Reply::Text(text) => socket.send(Message::Text(text.into())).expect("Failed to reply"),
+                    Reply::Close => {
+                        let _ = socket.close(None);
+                        let _ = socket.flush();
+                        break;
+                    }
+                }
+            }
+            received
+        });
+        (port, handle)
+    }
+
+    #[test]
+    fn cache_tracks_changed_lines() {
+        let mut cache = SendCache::default();
+        let sent = line("join", "{\"blocks\":[]}");
+        assert!(cache.is_changed(&sent));
+        cache.update(&sent);
+        assert!(!cache.is_changed(&sent));
+        assert!(cache.is_changed(&line("join", "{\"blocks\":[1]}")));
+        assert!(cache.is_changed(&line("leave", "{\"blocks\":[]}")));
+    }
+
+    #[test]
+    fn forgotten_lines_are_sent_again() {
+        let mut cache = SendCache::default();
+        let lines = [line("join", "a"), line("leave", "b")];
+        cache.update(&lines[0]);
+        cache.update(&lines[1]);
+        cache.forget(&lines[..1]);
+        assert!(cache.is_changed(&lines[0]));
+        assert!(!cache.is_changed(&lines[1]));
+    }
+
+    #[test]
+    fn cache_survives_saving_and_loading() {
+        let path = std::env::temp_dir().join("dfrs-test-send-cache.json");
+        let mut cache = SendCache::default();
+        let join = line("join", "a");
+        cache.update(&join);
+        cache.save(&path).expect("Failed to save cache");
+        assert!(!SendCache::load(&path).is_changed(&join));
+        let _ = fs::remove_file(&path);
+    }
+    #[test]
+    fn sends_changed_lines_to_codeclient() {
+        let (port, server) = fake_codeclient(|text| match text {
+            "scopes write_code" => Reply::Text("auth"),
+            "place go" => Reply::Text("place done"),
+            _ => Reply::Nothing
+        });
+        let mut cache = SendCache::default();
+        let report = send(vec![line("join", "a"), line("leave", "b")], &codeclient_config(port), &mut cache).expect("Failed to send");
+        assert_eq!(report.sent, 2);
+        let received = server.join().unwrap();
+        assert_eq!(received, ["scopes write_code".to_owned(), "place swap".to_owned(), format!("place {}", compress("a".into())), format!("place {}", compress("b".into())), "place go".to_owned()]);
+
+        // Nothing changed, so CodeClient isn't even connected to
+        let report = send(vec![line("join", "a")], &codeclient_config(port), &mut cache).expect("Failed to skip");
+        assert_eq!((report.sent, report.skipped), (0, vec!["join".to_owned()]));
+    }
+
+    #[test]
+    fn closing_instead_of_authorizing_is_a_refusal() {
+        let (port, _server) = fake_codeclient(|_| Reply::Close);
+        let result = send(vec![line("join", "a")], &codeclient_config(port), &mut SendCache::default());
+        assert!(matches!(result, Err(SendError::AuthRefused)));
+    }
+
+    #[test]
+    fn unanswered_auth_times_out_and_keeps_the_cache() {
+        let (port, _server) = fake_codeclient(|_| Reply::Nothing);
+        let mut cache = SendCache::default();
+        let result = send(vec![line("join", "a")], &codeclient_config(port), &mut cache);
+        assert!(matches!(result, Err(SendError::Timeout { .. })));
+        assert!(cache.is_changed(&line("join", "a")));
+    }
+
+    #[test]
+    fn nothing_listening_is_reported_as_not_running() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let result = send(vec![line("join", "a")], &codeclient_config(port), &mut SendCache::default());
+        let err = result.expect_err("Sending should fail");
+        assert!(matches!(err, SendError::NotRunning { .. }));
+        assert_eq!(err.to_string(), format!("Could not connect to CodeClient at 127.0.0.1:{port}, make sure Minecraft is running with CodeClient installed"));
+    }
+}
\ No newline at end of file
@@ -3,7 +3,7 @@ use std::fmt::Display;
 use phf::phf_map;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Position {
     pub line: u32,
     pub col: u32
@@ -56,6 +56,14 @@ pub enum Token {
     Dot,
     Comma,
     Equal,
+    EqualEqual,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    And,
+    Or,
     Semicolon,
     QuestionMark,
     Dollar,
@@ -85,6 +93,14 @@ impl Display for Token {
             Token::Dot => write!(f, "."),
             Token::Comma => write!(f, ","),
             Token::Equal => write!(f, "="),
+            Token::EqualEqual => write!(f, "=="),
+            Token::NotEqual => write!(f, "!="),
+            Token::Less => write!(f, "<"),
+            Token::LessEqual => write!(f, "<="),
+            Token::Greater => write!(f, ">"),
+            Token::GreaterEqual => write!(f, ">="),
+            Token::And => write!(f, "&&"),
+            Token::Or => write!(f, "||"),
             Token::Semicolon => write!(f, ";"),
             Token::QuestionMark => write!(f, "?"),
             Token::Dollar => write!(f, "$"),
@@ -115,6 +131,7 @@ pub enum Keyword {
     IfE,
     IfG,
     IfV,
+    If,
     Else,
     VarLine,
     VarLocal,
@@ -140,6 +157,7 @@ impl Display for Keyword {
             Keyword::IfE => write!(f, "ife"),
             Keyword::IfG => write!(f, "ifg"),
             Keyword::IfV => write!(f, "ifv"),
+            Keyword::If => write!(f, "if"),
             Keyword::Else => write!(f, "else"),
             Keyword::VarLine => write!(f, "line"),
             Keyword::VarLocal => write!(f, "local"),
@@ -165,6 +183,7 @@ pub static KEYWORDS: phf::Map<&'static str, Keyword> = phf_map! {
     "ife" => Keyword::IfE,
     "ifg" => Keyword::IfG,
     "ifv" => Keyword::IfV,
+    "if" => Keyword::If,
     "else" => Keyword::Else,
     "line" => Keyword::VarLine,
     "local" => Keyword::VarLocal,
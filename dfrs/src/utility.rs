@@ -1,3 +1,5 @@
+use sha1::{Digest, Sha1};
+
 pub fn to_camel_case(s: &str) -> String {
     let mut words = s.split_whitespace();
     let mut camel_case_string = String::new();
@@ -32,4 +34,36 @@ pub fn to_dfrs_name(s: &str) -> String {
     vv[0] = vv[0].to_lowercase().next().unwrap();
     let name: String = vv.into_iter().collect();
     name
+}
+
+/// Hex encoded sha1 of the data, used to detect changed templates
+pub fn hash(data: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(data.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// Number of single character edits needed to turn one string into the other
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let cost = if a_char == *b_char { 0 } else { 1 };
+            current.push((previous[j] + cost).min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The option closest to `name`, if it is close enough to be a likely typo
+pub fn suggest<'a>(name: &str, options: impl IntoIterator<Item = &'a str>) -> Option<String> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    options.into_iter()
+        .map(|option| (levenshtein(&name.to_lowercase(), &option.to_lowercase()), option))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, option)| option.to_owned())
 }
\ No newline at end of file
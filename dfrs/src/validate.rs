@@ -1,8 +1,29 @@
-use crate::{definitions::{action_dump::{Action, ActionDump}, ArgType, DefinedArg}, node::{ActionNode, ActionType, Arg, ArgValue, CallNode, ConditionalNode, ConditionalType, EventNode, Expression, FileNode, RepeatNode}, token::Position};
+use crate::{definitions::{action_dump::{Action, ActionDump}, ArgType, DefinedArg}, node::{ActionNode, ActionType, Arg, ArgValue, AssignNode, CallNode, ConditionalNode, ConditionalType, EventNode, Expression, FileNode, FunctionNode, RepeatNode}, token::{Position, Selector, SELECTORS}};
 use crate::definitions::action_dump::RawActionDump;
-use crate::definitions::events::{EntityEvents, PlayerEvents};
+use crate::diagnostic::Diagnostic;
+use crate::definitions::events::{is_event_selector, EntityEvents, PlayerEvents};
 use crate::definitions::game_values::GameValues;
-use crate::node::{ExpressionNode, StartNode};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::utility::suggest;
+use crate::node::{ExpressionNode, MathNode, MathOperator, ParticleData, StartNode, VariableNode};
+
+/// The particle tags of dfrs and the fields of the action dump that allow them
+const PARTICLE_FIELDS: [(&str, &str); 9] = [
+    ("motion", "Motion"),
+    ("motionVariation", "Motion Variation"),
+    ("rgb", "Color"),
+    ("rgbFade", "Fade Color"),
+    ("colorVariation", "Color Variation"),
+    ("material", "Material"),
+    ("size", "Size"),
+    ("sizeVariation", "Size Variation"),
+    ("roll", "Roll")
+];
 
 pub enum ValidateError {
     UnknownEvent { node: EventNode },
@@ -12,7 +33,124 @@ pub enum ValidateError {
     WrongArgumentType { args: Vec<Arg>, index: i32, name: String, expected_types: Vec<ArgType>, found_type: ArgType },
     TooManyArguments { name: String, start_pos: Position, end_pos: Position },
     InvalidTagOption { tag_name: String, provided: String, options: Vec<String>, start_pos: Position, end_pos: Position },
-    UnknownTag { tag_name: String, available: Vec<String>, start_pos: Position, end_pos: Position }
+    UnknownTag { tag_name: String, available: Vec<String>, start_pos: Position, end_pos: Position },
+    UnknownFunction { name: String, suggestion: Option<String>, start_pos: Position, end_pos: Position },
+    UnknownProcess { name: String, suggestion: Option<String>, unparsed_files: Vec<String>, start_pos: Position, end_pos: Position },
+    /// An error in the arguments of a call to a function of the file, pointing at the definition of the function
+    FunctionArgument { err: Box<ValidateError>, function: String, start_pos: Position, end_pos: Position },
+    VariableTypeMismatch { name: String, declared: ArgType, found: ArgType, start_pos: Position, end_pos: Position, declared_start_pos: Position, declared_end_pos: Position },
+    EventNotCancellable { event: String, start_pos: Position, end_pos: Position },
+    UnavailableSelector { selector: String, event: String, available: Vec<String>, start_pos: Position, end_pos: Position },
+    NoMatchingOverload { name: String, overloads: Vec<String>, start_pos: Position, end_pos: Position },
+    UnknownValueName { kind: String, name: String, suggestion: Option<String>, start_pos: Position, end_pos: Position },
+    UnsupportedParticleField { particle: String, field: String, supported: Vec<String>, start_pos: Position, end_pos: Position },
+    ParticleFieldOutOfRange { field: String, min: f32, max: Option<f32>, start_pos: Position, end_pos: Position }
+}
+
+impl From<ValidateError> for Diagnostic {
+    fn from(err: ValidateError) -> Diagnostic {
+        match err {
+            ValidateError::UnknownEvent { node } => {
+                Diagnostic::error("E0201", format!("Unknown event '{}'", node.event))
+                    .with_span(node.start_pos, node.name_end_pos)
+            }
+            ValidateError::UnknownAction { name, start_pos, end_pos } => {
+                Diagnostic::error("E0202", format!("Unknown action '{name}'")).with_span(start_pos, end_pos)
+            }
+            ValidateError::UnknownGameValue { start_pos, end_pos, game_value } => {
+                Diagnostic::error("E0203", format!("Unknown game value '{game_value}'")).with_span(start_pos, end_pos)
+            }
+            ValidateError::MissingArgument { name, start_pos, end_pos } => {
+                Diagnostic::error("E0204", format!("Missing argument '{name}'")).with_span(start_pos, end_pos)
+            }
+            ValidateError::WrongArgumentType { args, index, name, expected_types, found_type } => {
+                let arg = args.get(index as usize).unwrap();
+                Diagnostic::error("E0205", format!("Wrong argument type for '{name}'"))
+                    .with_span(arg.start_pos.clone(), arg.end_pos.clone())
+                    .with_help(format!("expected {expected_types:?} but found {found_type:?}"))
+            }
+            ValidateError::TooManyArguments { name, start_pos, end_pos } => {
+                Diagnostic::error("E0206", format!("Too many arguments for action '{name}'")).with_span(start_pos, end_pos)
+            }
+            ValidateError::InvalidTagOption { tag_name, provided, options, start_pos, end_pos } => {
+                Diagnostic::error("E0207", format!("Invalid option '{provided}' for tag '{tag_name}'"))
+                    .with_span(start_pos, end_pos)
+                    .with_help(format!("expected one of {options:?}"))
+            }
+            ValidateError::UnknownTag { tag_name, available, start_pos, end_pos } => {
+                Diagnostic::error("E0208", format!("Unknown tag '{tag_name}'"))
+                    .with_span(start_pos, end_pos)
+                    .with_help(format!("available tags are {available:?}"))
+            }
+            ValidateError::UnknownFunction { name, suggestion, start_pos, end_pos } => {
+                let diagnostic = Diagnostic::error("E0209", format!("Unknown function '{name}'")).with_span(start_pos, end_pos);
+                match suggestion {
+                    Some(suggestion) => diagnostic.with_help(format!("a function with a similar name exists: '{suggestion}'")),
+                    None => diagnostic
+                }
+            }
+            ValidateError::UnknownProcess { name, suggestion, unparsed_files, start_pos, end_pos } => {
+                let diagnostic = Diagnostic::error("E0210", format!("Unknown process '{name}'")).with_span(start_pos, end_pos);
+                match suggestion {
+                    Some(suggestion) => diagnostic.with_help(format!("a process with a similar name exists: '{suggestion}'")),
+                    None if !unparsed_files.is_empty() => diagnostic.with_help(format!("it may be declared in a file that could not be parsed: {}", unparsed_files.join(", "))),
+                    None => diagnostic
+                }
+            }
+            ValidateError::FunctionArgument { err, function, start_pos, end_pos } => {
+                Diagnostic::from(*err).with_label(start_pos, end_pos, format!("'{function}' is defined here"))
+            }
+            ValidateError::VariableTypeMismatch { name, declared, found, start_pos, end_pos, declared_start_pos, declared_end_pos } => {
+                Diagnostic::error("E0211", format!("Variable '{name}' is set to a value of the wrong type"))
+                    .with_span(start_pos, end_pos)
+                    .with_label(declared_start_pos, declared_end_pos, format!("declared as {declared:?} here"))
+                    .with_help(format!("the variable is declared as {declared:?} but the value is {found:?}"))
+            }
+            ValidateError::EventNotCancellable { event, start_pos, end_pos } => {
+                Diagnostic::error("E0216", format!("Event '{event}' cannot be cancelled"))
+                    .with_span(start_pos, end_pos)
+                    .with_help(format!("remove the '!' after '@{event}'"))
+            }
+            ValidateError::UnavailableSelector { selector, event, available, start_pos, end_pos } => {
+                let diagnostic = Diagnostic::warning("W0201", format!("Selector '{selector}' is not available in event '{event}'"))
+                    .with_span(start_pos, end_pos);
+                if available.is_empty() {
+                    diagnostic.with_help("it will not select anything, this event has no event specific selectors".into())
+                } else {
+                    diagnostic.with_help(format!("it will not select anything, this event provides {}", available.join(", ")))
+                }
+            }
+            ValidateError::NoMatchingOverload { name, overloads, start_pos, end_pos } => {
+                Diagnostic::error("E0212", format!("No overload of '{name}' matches the given arguments"))
+                    .with_span(start_pos, end_pos)
+                    .with_help(format!("expected one of:\n  {}", overloads.join("\n  ")))
+            }
+            ValidateError::UnknownValueName { kind, name, suggestion, start_pos, end_pos } => {
+                let diagnostic = Diagnostic::error("E0213", format!("Unknown {kind} '{name}'")).with_span(start_pos, end_pos);
+                match suggestion {
+                    Some(suggestion) => diagnostic.with_help(format!("a {kind} with a similar name exists: '{suggestion}'")),
+                    None => diagnostic
+                }
+            }
+            ValidateError::UnsupportedParticleField { particle, field, supported, start_pos, end_pos } => {
+                let diagnostic = Diagnostic::error("E0214", format!("Particle '{particle}' does not support '{field}'")).with_span(start_pos, end_pos);
+                if supported.is_empty() {
+                    diagnostic.with_help("this particle does not take any data".into())
+                } else {
+                    diagnostic.with_help(format!("supported fields are {}", supported.join(", ")))
+                }
+            }
+            ValidateError::ParticleFieldOutOfRange { field, min, max, start_pos, end_pos } => {
+                let expected = match max {
+                    Some(max) => format!("a value from {min} to {max}"),
+                    None => format!("a value of at least {min}")
+                };
+                Diagnostic::error("E0215", format!("Particle field '{field}' is out of range"))
+                    .with_span(start_pos, end_pos)
+                    .with_help(format!("expected {expected}"))
+            }
+        }
+    }
 }
 
 pub struct Validator {
@@ -21,7 +159,26 @@ pub struct Validator {
 
     action_dump: ActionDump,
 
-    game_values: GameValues
+    game_values: GameValues,
+
+    functions: Vec<FunctionNode>,
+    /// Processes declared in other files of the project
+    project_processes: Vec<String>,
+    /// Other files of the project that could not be parsed, their processes are unknown
+    unparsed_files: Vec<String>,
+    processes: Vec<String>,
+
+    /// Types of variables by name and scope, from `line name: type;` declarations, with the span of the declaration
+    declared_types: HashMap<(String, String), (ArgType, Position, Position)>,
+    /// Types of variables by name and scope, from the set variable actions that last set them
+    inferred_types: HashMap<(String, String), ArgType>,
+
+    /// Name and event specific selectors of the event being validated, None inside functions, processes and events whose selectors are not known
+    event: Option<(String, Vec<Selector>)>,
+
+    errors: Vec<ValidateError>,
+    /// Problems that don't stop the file from compiling
+    warnings: Vec<ValidateError>
 }
 
 impl Validator {
@@ -33,73 +190,190 @@ impl Validator {
 
             action_dump: ActionDump::new(&action_dump),
 
-            game_values: GameValues::new(&action_dump)
+            game_values: GameValues::new(&action_dump),
+
+            functions: vec![],
+            project_processes: vec![],
+            unparsed_files: vec![],
+            processes: vec![],
+
+            declared_types: HashMap::new(),
+            inferred_types: HashMap::new(),
+
+            event: None,
+
+            errors: vec![],
+            warnings: vec![]
         }
     }
-    pub fn validate(&self, mut node: FileNode) -> Result<FileNode, ValidateError> {
+
+    /// Takes the warnings found while validating, they are kept even if validation fails
+    pub fn take_warnings(&mut self) -> Vec<ValidateError> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Makes the processes of the other files of the project available to `file`
+    pub fn with_project(mut self, project: &ProjectProcesses, file: &Path) -> Validator {
+        (self.project_processes, self.unparsed_files) = project.for_file(file);
+        self
+    }
+
+    pub fn validate(&mut self, mut node: FileNode) -> Result<FileNode, Vec<ValidateError>> {
+        self.functions = node.functions.iter().map(|function| FunctionNode { expressions: vec![], ..function.clone() }).collect();
+        self.processes = node.processes.iter().map(|process| process.name.clone()).chain(self.project_processes.clone()).collect();
+
+        let mut global_types = HashMap::new();
+        for variable in &node.variables {
+            if let Some(value_type) = declared_type(variable) {
+                global_types.insert((variable.df_name.clone(), variable.var_type.scope().to_owned()), (value_type, variable.start_pos.clone(), variable.end_pos.clone()));
+            }
+        }
+
+        self.event = None;
         for function in node.functions.iter_mut() {
+            self.declared_types.clone_from(&global_types);
+            self.inferred_types.clear();
+            for param in function.params.iter_mut() {
+                if let Some(default) = &mut param.default {
+                    if let Err(err) = self.validate_value(&mut default.value, &default.start_pos, &default.end_pos) {
+                        self.errors.push(err);
+                    }
+                }
+                let value_type = ArgType::from(&param.param_type);
+                if value_type != ArgType::ANY && value_type != ArgType::VARIABLE {
+                    self.declared_types.insert((param.name.clone(), "line".into()), (value_type, param.start_pos.clone(), param.end_pos.clone()));
+                }
+            }
             for expression in function.expressions.iter_mut() {
-                self.validate_expression_node(expression)?;
+                self.validate_expression_node(expression);
             }
         }
 
         for process in node.processes.iter_mut() {
+            self.declared_types.clone_from(&global_types);
+            self.inferred_types.clear();
             for expression in process.expressions.iter_mut() {
-                self.validate_expression_node(expression)?;
+                self.validate_expression_node(expression);
             }
         }
 
         for event in node.events.iter_mut() {
-            let mut actual_event;
-            
-            actual_event = self.player_events.get(event.event.clone());
-            match actual_event {
-                Some(actual) => {
+            self.declared_types.clone_from(&global_types);
+            self.inferred_types.clear();
+            let actual = self.player_events.get(event.event.clone()).map(|actual| (actual, ActionType::Player))
+                .or_else(|| self.entity_events.get(event.event.clone()).map(|actual| (actual, ActionType::Entity)));
+            match actual {
+                Some((actual, event_type)) => {
+                    if event.cancelled && !actual.cancellable {
+                        self.errors.push(ValidateError::EventNotCancellable { event: event.event.clone(), start_pos: event.start_pos.clone(), end_pos: event.name_end_pos.clone() });
+                    }
+                    self.event = actual.selectors.clone().map(|selectors| (actual.dfrs_name.clone(), selectors));
                     actual.df_name.clone_into(&mut event.event);
-                    event.event_type = Some(ActionType::Player);
+                    event.event_type = Some(event_type);
                 }
                 None => {
-                    actual_event = self.entity_events.get(event.event.clone());
-                    match actual_event {
-                        Some(actual) => {
-                            actual.df_name.clone_into(&mut event.event);
-                            event.event_type = Some(ActionType::Entity);
-                        }
-                        None => {
-                            return Err(ValidateError::UnknownEvent { node: event.clone() })
-                        }
-                    }
+                    self.event = None;
+                    self.errors.push(ValidateError::UnknownEvent { node: event.clone() })
                 }
             }
 
             for expression in event.expressions.iter_mut() {
-                self.validate_expression_node(expression)?
+                self.validate_expression_node(expression);
             }
         }
 
+        if !self.errors.is_empty() {
+            return Err(std::mem::take(&mut self.errors));
+        }
         Ok(node)
     }
 
-    fn validate_expression_node(&self, mut expression_node: &mut ExpressionNode) -> Result<(), ValidateError> {
-        match expression_node.node.clone() {
+    fn validate_expression_node(&mut self, expression_node: &mut ExpressionNode) {
+        let args = match &expression_node.node {
+            Expression::Action { node } => node.args.clone(),
+            Expression::Conditional { node } => node.args.clone(),
+            Expression::Call { node } => node.args.clone(),
+            Expression::Start { node } => node.args.clone(),
+            Expression::Repeat { node } => node.args.clone(),
+            Expression::Assign { node } => math_args(&node.value),
+            Expression::Group { .. } | Expression::Variable { .. } => vec![]
+        };
+        self.check_arg_selectors(&args);
+
+        let result = match expression_node.node.clone() {
             Expression::Action { node } => {
-                expression_node.node = Expression::Action { node: self.validate_action_node(node)? };
+                // The action starts at its selector, the selector span of the node is kept as the lsp expects it
+                let mut selector_end_pos = node.start_pos.clone();
+                selector_end_pos.col += selector_name(&node.selector).len() as u32;
+                self.check_selector(&node.selector, &node.start_pos, &selector_end_pos);
+                let name = node.name.clone();
+                self.validate_action_node(node).and_then(|node| {
+                    self.set_variable_type(&name, &node)?;
+                    Ok(Expression::Action { node })
+                })
             }
             Expression::Conditional { node } => {
-                expression_node.node = Expression::Conditional { node: self.validate_conditional_node(node)? }
+                if let (Some(start_pos), Some(end_pos)) = (&node.selector_start_pos, &node.selector_end_pos) {
+                    self.check_selector(&node.selector, start_pos, end_pos);
+                }
+                self.validate_conditional_node(node).map(|node| Expression::Conditional { node })
             }
             Expression::Call { node } => {
-                expression_node.node = Expression::Call { node: self.validate_call(node)? }
+                self.validate_call(node).map(|node| Expression::Call { node })
             }
             Expression::Start { node } => {
-                expression_node.node = Expression::Start { node: self.validate_start(node)? }
+                self.validate_start(node).map(|node| Expression::Start { node })
             }
             Expression::Repeat { node } => {
-                expression_node.node = Expression::Repeat { node: self.validate_repeat_node(node)? }
+                self.validate_repeat_node(node).map(|node| Expression::Repeat { node })
+            }
+            Expression::Assign { node } => {
+                self.validate_assign(*node).map(|node| Expression::Assign { node: Box::new(node) })
+            }
+            Expression::Group { mut node } => {
+                for expression in node.expressions.iter_mut() {
+                    self.validate_expression_node(expression);
+                }
+                Ok(Expression::Group { node })
+            }
+            Expression::Variable { node } => {
+                if let Some(value_type) = declared_type(&node) {
+                    self.declared_types.insert((node.df_name, node.var_type.scope().to_owned()), (value_type, node.start_pos, node.end_pos));
+                }
+                return
+            }
+        };
+        match result {
+            Ok(node) => expression_node.node = node,
+            Err(err) => self.errors.push(err)
+        }
+    }
+
+    /// Warns about event specific selectors the current event doesn't provide, they don't select anything in game
+    fn check_selector(&mut self, selector: &Selector, start_pos: &Position, end_pos: &Position) {
+        let Some((event, selectors)) = &self.event else {
+            return
+        };
+        if !is_event_selector(selector) || selectors.contains(selector) {
+            return
+        }
+        let name = |selector: &Selector| selector_name(selector).to_owned();
+        let warning = ValidateError::UnavailableSelector { selector: name(selector), event: event.clone(), available: selectors.iter().map(name).collect(), start_pos: start_pos.clone(), end_pos: end_pos.clone() };
+        self.warnings.push(warning);
+    }
+
+    /// Checks the selectors of game values and conditions passed as arguments
+    fn check_arg_selectors(&mut self, args: &[Arg]) {
+        for arg in args {
+            match &arg.value {
+                ArgValue::GameValue { selector, selector_end_pos, .. } => self.check_selector(selector, &arg.start_pos, selector_end_pos),
+                ArgValue::Condition { selector, args, .. } => {
+                    self.check_selector(selector, &arg.start_pos, &arg.end_pos);
+                    self.check_arg_selectors(args);
+                }
+                _ => {}
             }
-            Expression::Variable { .. } => {}
         }
-        Ok(())
     }
 
     fn validate_action_node(&self, mut action_node: ActionNode) -> Result<ActionNode, ValidateError> {
@@ -172,11 +446,163 @@ impl Validator {
 
     fn validate_action(&self, mut action_node: ActionNode, action: &Action) -> Result<ActionNode, ValidateError> {
         action_node.name.clone_from(&action.df_name);
-        action_node.args = self.validate_args(action_node.args, action, action_node.start_pos.clone(), action_node.end_pos.clone())?;
+        action_node.args = self.validate_args(action_node.args, action, action_node.start_pos.clone(), action_node.args_end_pos.clone())?;
         Ok(action_node)
     }
 
-    fn validate_conditional_node(&self, mut conditional_node: ConditionalNode) -> Result<ConditionalNode, ValidateError> {
+    fn validate_assign(&mut self, mut assign_node: AssignNode) -> Result<AssignNode, ValidateError> {
+        let mut actions = vec![];
+        let mut temps = 0;
+        self.lower_math(&assign_node, assign_node.variable.clone(), assign_node.value.clone(), &mut actions, &mut temps);
+
+        assign_node.actions = vec![];
+        for action in actions {
+            let name = action.name.clone();
+            let action = self.validate_action_node(action)?;
+            self.set_variable_type(&name, &action)?;
+            assign_node.actions.push(action);
+        }
+        Ok(assign_node)
+    }
+
+    fn variable_type(&self, arg: &Arg) -> Option<ArgType> {
+        match &arg.value {
+            ArgValue::Variable { name, scope } => {
+                let key = (name.clone(), scope.clone());
+                self.declared_types.get(&key).map(|(declared, _, _)| declared).or_else(|| self.inferred_types.get(&key)).cloned()
+            }
+            _ => None
+        }
+    }
+
+    /// Remembers the type of the value a set variable action stores, or errors if it doesn't match the declared type
+    fn set_variable_type(&mut self, dfrs_name: &str, node: &ActionNode) -> Result<(), ValidateError> {
+        if node.action_type != ActionType::Variable {
+            return Ok(())
+        }
+        let Some(Arg { value: ArgValue::Variable { name, scope }, .. }) = node.args.first() else {
+            return Ok(())
+        };
+        let Some(action) = self.action_dump.variable_actions.get(dfrs_name.to_owned()) else {
+            return Ok(())
+        };
+
+        // actions like = return whatever they are given
+        let values: Vec<&Arg> = node.args.iter().skip(1).filter(|arg| arg.arg_type != ArgType::TAG && arg.arg_type != ArgType::EMPTY).collect();
+        let value_type = match &action.return_type {
+            Some(ArgType::ANY) | None if values.len() == 1 => self.variable_type(values[0]).unwrap_or(values[0].arg_type.clone()),
+            Some(return_type) => return_type.clone(),
+            None => ArgType::ANY
+        };
+
+        let key = (name.clone(), scope.clone());
+        if matches!(value_type, ArgType::ANY | ArgType::VARIABLE | ArgType::EMPTY) {
+            self.inferred_types.remove(&key);
+            return Ok(())
+        }
+        if let Some((declared, declared_start_pos, declared_end_pos)) = self.declared_types.get(&key) {
+            if *declared != value_type {
+                return Err(ValidateError::VariableTypeMismatch {
+                    name: name.clone(),
+                    declared: declared.clone(),
+                    found: value_type,
+                    start_pos: node.start_pos.clone(),
+                    end_pos: node.end_pos.clone(),
+                    declared_start_pos: declared_start_pos.clone(),
+                    declared_end_pos: declared_end_pos.clone()
+                })
+            }
+            return Ok(())
+        }
+        self.inferred_types.insert(key, value_type);
+        Ok(())
+    }
+
+    /// Lowers a math expression into set variable actions storing the result in `target`
+    fn lower_math(&self, assign_node: &AssignNode, target: Arg, value: MathNode, actions: &mut Vec<ActionNode>, temps: &mut i32) {
+        let make_action = |name: &str, mut args: Vec<Arg>| {
+            args.insert(0, target.clone());
+            ActionNode {
+                action_type: ActionType::Variable,
+                selector: Selector::Default,
+                name: name.into(),
+                args,
+                start_pos: assign_node.start_pos.clone(),
+                selector_start_pos: assign_node.start_pos.clone(),
+                selector_end_pos: assign_node.start_pos.clone(),
+                end_pos: assign_node.end_pos.clone(),
+                args_end_pos: assign_node.end_pos.clone()
+            }
+        };
+
+        let operator = match &value {
+            MathNode::Value { arg } => {
+                actions.push(make_action("equal", vec![arg.clone()]));
+                return
+            }
+            MathNode::Binary { operator, .. } => operator.clone()
+        };
+
+        if let Some(expression) = math_text(&value) {
+            let arg = Arg {
+                value: ArgValue::ComplexNumber { number: format!("%math({expression})") },
+                index: 0,
+                arg_type: ArgType::NUMBER,
+                start_pos: value.start_pos(),
+                end_pos: value.end_pos()
+            };
+            actions.push(make_action("equal", vec![arg]));
+            return
+        }
+
+        let mut args = vec![];
+        for operand in flatten_math(value, &operator) {
+            match operand {
+                MathNode::Value { arg } => args.push(arg),
+                binary => {
+                    *temps += 1;
+                    let temp = Arg {
+                        value: ArgValue::Variable { name: format!("__dfrs_tmp{temps}"), scope: "line".into() },
+                        index: 0,
+                        arg_type: ArgType::VARIABLE,
+                        start_pos: binary.start_pos(),
+                        end_pos: binary.end_pos()
+                    };
+                    self.lower_math(assign_node, temp.clone(), binary, actions, temps);
+                    args.push(temp);
+                }
+            }
+        }
+
+        let name = match operator {
+            MathOperator::Add => "add",
+            MathOperator::Sub => "sub",
+            MathOperator::Mul => "mul",
+            MathOperator::Div => "div"
+        };
+        actions.push(make_action(name, args));
+    }
+
+    fn validate_conditional_node(&mut self, mut conditional_node: ConditionalNode) -> Result<ConditionalNode, ValidateError> {
+        // Each branch starts with the types known before the conditional, afterwards only the types both branches agree on are kept
+        let before = self.inferred_types.clone();
+        for expression in conditional_node.expressions.iter_mut() {
+            self.validate_expression_node(expression);
+        }
+        let then_types = std::mem::replace(&mut self.inferred_types, before.clone());
+
+        for expression in conditional_node.else_expressions.iter_mut() {
+            self.validate_expression_node(expression);
+        }
+        let else_types = std::mem::replace(&mut self.inferred_types, before);
+
+        let result = self.validate_conditional_head(conditional_node);
+        self.inferred_types = merge_types(then_types, &else_types);
+        result
+    }
+
+    fn validate_conditional_head(&self, mut conditional_node: ConditionalNode) -> Result<ConditionalNode, ValidateError> {
+
         let action = match conditional_node.conditional_type {
             ConditionalType::Player => {
                 self.action_dump.player_conditionals.get(conditional_node.clone().name)
@@ -197,14 +623,6 @@ impl Validator {
             None => return Err(ValidateError::UnknownAction { name: conditional_node.name, start_pos: conditional_node.start_pos, end_pos: conditional_node.end_pos })
         };
 
-        for expression in conditional_node.expressions.iter_mut() {
-            self.validate_expression_node(expression)?;
-        }
-
-        for expression in conditional_node.else_expressions.iter_mut() {
-            self.validate_expression_node(expression)?;
-        }
-
         Ok(conditional_node)
     }
 
@@ -215,33 +633,59 @@ impl Validator {
     }
 
     fn validate_call(&self, mut call_node: CallNode) -> Result<CallNode, ValidateError> {
-        // TODO proper validation
-        let mut args = vec![];
-        for arg in &call_node.args {
-            args.push(DefinedArg {
-                arg_types: vec![ArgType::ANY],
-                name: "".into(),
-                allow_multiple: false,
-                optional: false,
-            })
-        }
-        let action = Action {
-            df_name: "internal".into(),
-            dfrs_name: "internal".into(),
-            args,
-            tags: vec![],
-            has_conditional_arg: false
+        let function = if call_node.direct {
+            match self.functions.iter().find(|function| function.dfrs_name == call_node.name) {
+                Some(function) => Some(function),
+                None => {
+                    let suggestion = suggest(&call_node.name, self.functions.iter().map(|function| function.dfrs_name.as_str()));
+                    let mut end_pos = call_node.start_pos.clone();
+                    end_pos.col += call_node.name.len() as u32;
+                    return Err(ValidateError::UnknownFunction { name: call_node.name, suggestion, start_pos: call_node.start_pos, end_pos })
+                }
+            }
+        } else {
+            // call() can also target functions from other files, those can't be checked
+            self.functions.iter().find(|function| function.df_name == call_node.name)
+        };
+
+        let Some(function) = function else {
+            let args = call_node.args.iter().map(|_| DefinedArg::new("", vec![ArgType::ANY], false, false)).collect();
+            let action = Action::new("internal".into(), "internal", args, vec![], false);
+            call_node.args = self.validate_args(call_node.args, &action, call_node.start_pos.clone(), call_node.end_pos.clone())?;
+            return Ok(call_node)
         };
-        call_node.args = self.validate_args(call_node.args, &action, call_node.start_pos.clone(), call_node.end_pos.clone())?;
+
+        call_node.name.clone_from(&function.df_name);
+        let args = function.params.iter()
+            .map(|param| DefinedArg::new(&param.name, vec![ArgType::from(&param.param_type)], param.optional, param.multiple))
+            .collect();
+        let action = Action::new(function.dfrs_name.clone(), &function.df_name, args, vec![], false);
+        call_node.args = self.validate_args(call_node.args, &action, call_node.start_pos.clone(), call_node.end_pos.clone()).map_err(|err| {
+            let mut start_pos = function.name_end_pos.clone();
+            start_pos.col -= function.dfrs_name.len() as u32;
+            ValidateError::FunctionArgument { err: Box::new(err), function: function.dfrs_name.clone(), start_pos, end_pos: function.name_end_pos.clone() }
+        })?;
         Ok(call_node)
     }
 
     fn validate_start(&self, mut start_node: StartNode) -> Result<StartNode, ValidateError> {
+        if !self.processes.contains(&start_node.name) {
+            let suggestion = suggest(&start_node.name, self.processes.iter().map(|process| process.as_str()));
+            return Err(ValidateError::UnknownProcess { name: start_node.name, suggestion, unparsed_files: self.unparsed_files.clone(), start_pos: start_node.name_start_pos, end_pos: start_node.name_end_pos })
+        }
         start_node.args = self.validate_args(start_node.args, &self.action_dump.start_process_action, start_node.start_pos.clone(), start_node.end_pos.clone())?;
         Ok(start_node)
     }
 
-    fn validate_repeat_node(&self, mut repeat_node: RepeatNode) -> Result<RepeatNode, ValidateError> {
+    fn validate_repeat_node(&mut self, mut repeat_node: RepeatNode) -> Result<RepeatNode, ValidateError> {
+        // The body may run any number of times, so only the types it doesn't change are known after it and in the condition
+        let before = self.inferred_types.clone();
+        for expression in repeat_node.expressions.iter_mut() {
+            self.validate_expression_node(expression);
+        }
+        let body_types = std::mem::take(&mut self.inferred_types);
+        self.inferred_types = merge_types(body_types, &before);
+
         let mut action = self.action_dump.repeats.get(repeat_node.clone().name);
         let mut old_args = vec![];
         let mut old_name = "".into();
@@ -285,10 +729,6 @@ impl Validator {
             }
         }
 
-        for expression in repeat_node.expressions.iter_mut() {
-            self.validate_expression_node(expression)?;
-        }
-
         Ok(repeat_node)
     }
 
@@ -298,14 +738,163 @@ impl Validator {
         Ok(repeat_node)
     }
 
-    fn validate_args(&self, input_args: Vec<Arg>, action: &Action, start_pos: Position, end_pos: Position) -> Result<Vec<Arg>, ValidateError> {
+    fn validate_args(&self, mut input_args: Vec<Arg>, action: &Action, start_pos: Position, end_pos: Position) -> Result<Vec<Arg>, ValidateError> {
+        for arg in input_args.iter_mut() {
+            self.validate_value(&mut arg.value, &arg.start_pos, &arg.end_pos)?;
+        }
+
+        let mut errors = vec![];
+        let mut matched = None;
+        for overload in &action.overloads {
+            match self.match_args(input_args.clone(), overload, &action.dfrs_name, start_pos.clone(), end_pos.clone()) {
+                Ok(result) => {
+                    matched = Some(result);
+                    break;
+                }
+                Err(err) => errors.push(err)
+            }
+        }
+        let (mut args, tags) = match matched {
+            Some(matched) => matched,
+            None if errors.len() == 1 => return Err(errors.remove(0)),
+            None => {
+                let overloads = action.overloads.iter().map(|overload| signature(overload)).collect();
+                return Err(ValidateError::NoMatchingOverload { name: action.dfrs_name.clone(), overloads, start_pos, end_pos })
+            }
+        };
+
+        for given_tag in tags.clone() {
+            match given_tag.value {
+                ArgValue::Tag { tag: tag_name, value: _, definition: _, name_end_pos, value_start_pos: _ } => {
+                    let mut found = false;
+                    let mut available = vec![];
+                    for tag in action.tags.clone() {
+                        available.push(tag.dfrs_name.clone());
+                        if tag.dfrs_name == tag_name {
+                            found = true;
+                        }
+                    }
+                    if !found {
+                        return Err(ValidateError::UnknownTag { tag_name, available, start_pos: given_tag.start_pos, end_pos: name_end_pos });
+                    }
+                }
+                _ => unreachable!()
+            }
+        }
+
+        for tag in action.tags.clone() {
+            let mut matched = false;
+            for given_tag in tags.clone() {
+                match given_tag.value {
+                    ArgValue::Tag { tag: tag_name, value, name_end_pos, value_start_pos , ..} => {
+                        let actual = match value.clone().as_ref() {
+                            ArgValue::Text { text } => text.clone(),
+                            err => return Err(ValidateError::InvalidTagOption { tag_name, provided: format!("{err:?}"), options: tag.options, start_pos: value_start_pos, end_pos: given_tag.end_pos })
+                        };
+                        if tag.dfrs_name == tag_name {
+                            if tag.options.contains(&actual) {
+                                matched = true;
+                                args.push(Arg {
+                                    arg_type: ArgType::TAG,
+                                    value: ArgValue::Tag { tag: tag.df_name.clone(), value, definition: Some(tag.clone()), name_end_pos, value_start_pos },
+                                    index: tag.slot as i32,
+                                    start_pos: given_tag.start_pos,
+                                    end_pos: given_tag.end_pos
+                                });
+                            } else {
+                                return Err(ValidateError::InvalidTagOption { tag_name, provided: actual, options: tag.options, start_pos: value_start_pos, end_pos: given_tag.end_pos });
+                            }
+                        }
+                    }
+                    _ => unreachable!()
+                }
+            }
+            if !matched {
+                let data = Box::new(ArgValue::Text {text:tag.default.clone()});
+                args.push(Arg {
+                    arg_type: ArgType::TAG,
+                    value: ArgValue::Tag { tag: tag.df_name.clone(), value: data, definition: Some(tag.clone()), name_end_pos: Position::new(0, 0), value_start_pos: Position::new(0, 0) },
+                    index: tag.slot as i32,
+                    start_pos: Position::new(0, 0),
+                    end_pos: Position::new(0, 0)
+                });
+            }
+        }
+
+        Ok(args)
+    }
+
+    /// Checks sound, potion and particle names and particle data against the action dump, names are matched ignoring case and changed to the dump's spelling
+    fn validate_value(&self, value: &mut ArgValue, start_pos: &Position, end_pos: &Position) -> Result<(), ValidateError> {
+        let (kind, name, names) = match value {
+            ArgValue::Sound { sound, .. } => ("sound", sound, self.action_dump.sounds.names()),
+            ArgValue::Potion { potion, .. } => ("potion", potion, self.action_dump.potions.names()),
+            ArgValue::Particle { particle, .. } => ("particle", particle, self.action_dump.particles.names()),
+            _ => return Ok(())
+        };
+        match names.iter().find(|known| known.eq_ignore_ascii_case(name)) {
+            Some(known) => name.clone_from(known),
+            None => {
+                let suggestion = suggest(name, names.iter().map(|name| name.as_str()));
+                return Err(ValidateError::UnknownValueName { kind: kind.into(), name: name.clone(), suggestion, start_pos: start_pos.clone(), end_pos: end_pos.clone() })
+            }
+        }
+        if let ArgValue::Particle { particle, data, .. } = value {
+            self.validate_particle_data(particle, data, start_pos, end_pos)?;
+        }
+        Ok(())
+    }
+
+    fn validate_particle_data(&self, particle: &str, data: &ParticleData, start_pos: &Position, end_pos: &Position) -> Result<(), ValidateError> {
+        let fields = &self.action_dump.particles.get(particle.to_owned()).unwrap().fields;
+        let given = [
+            ("motion", data.x.is_some() || data.y.is_some() || data.z.is_some()),
+            ("motionVariation", data.motion_variation.is_some()),
+            ("rgb", data.rgb.is_some()),
+            ("rgbFade", data.rgb_fade.is_some()),
+            ("colorVariation", data.color_variation.is_some()),
+            ("material", data.material.is_some()),
+            ("size", data.size.is_some()),
+            ("sizeVariation", data.size_variation.is_some()),
+            ("roll", data.roll.is_some())
+        ];
+        let supported: Vec<String> = PARTICLE_FIELDS.iter()
+            .filter(|(_, field)| fields.iter().any(|supported| supported == field))
+            .map(|(tag, _)| tag.to_string())
+            .collect();
+        for (tag, is_given) in given {
+            if is_given && !supported.iter().any(|supported| supported == tag) {
+                return Err(ValidateError::UnsupportedParticleField { particle: particle.to_owned(), field: tag.to_owned(), supported, start_pos: start_pos.clone(), end_pos: end_pos.clone() })
+            }
+        }
+
+        let ranges = [
+            ("motionVariation", data.motion_variation.map(|value| value as f32), 0.0, Some(100.0)),
+            ("rgb", data.rgb.map(|value| value as f32), 0.0, Some(16777215.0)),
+            ("rgbFade", data.rgb_fade.map(|value| value as f32), 0.0, Some(16777215.0)),
+            ("colorVariation", data.color_variation.map(|value| value as f32), 0.0, Some(100.0)),
+            ("size", data.size, 0.0, None),
+            ("sizeVariation", data.size_variation.map(|value| value as f32), 0.0, Some(100.0))
+        ];
+        for (field, value, min, max) in ranges {
+            if let Some(value) = value {
+                if value < min || max.is_some_and(|max| value > max) {
+                    return Err(ValidateError::ParticleFieldOutOfRange { field: field.to_owned(), min, max, start_pos: start_pos.clone(), end_pos: end_pos.clone() })
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Matches the given arguments against one overload, returning the matched arguments and the tags
+    fn match_args(&self, input_args: Vec<Arg>, overload: &[DefinedArg], name: &str, start_pos: Position, end_pos: Position) -> Result<(Vec<Arg>, Vec<Arg>), ValidateError> {
         let mut node_args = input_args;
         let all_provided_args: Vec<Arg> = node_args.clone();
         let mut args: Vec<Arg> = vec![];
         let mut index: i32 = -1;
 
         let mut tags: Vec<Arg> = vec![];
-        for arg in action.args.clone() {
+        for arg in overload {
             let mut match_more = true;
             let mut matched_one = false;
             while match_more {
@@ -326,7 +915,7 @@ impl Validator {
                         }
                         break;
                     } else if !matched_one {
-                        return Err(ValidateError::MissingArgument { name: arg.name, start_pos, end_pos })
+                        return Err(ValidateError::MissingArgument { name: arg.name.clone(), start_pos, end_pos })
                     } else {
                         break;
                     }
@@ -340,7 +929,7 @@ impl Validator {
                 }
 
                 if provided_arg.arg_type == ArgType::EMPTY && !arg.optional {
-                    return Err(ValidateError::MissingArgument { name: arg.name, start_pos, end_pos })
+                    return Err(ValidateError::MissingArgument { name: arg.name.clone(), start_pos, end_pos })
                 }
 
                 if let ArgValue::GameValue { df_name, dfrs_name, selector, selector_end_pos } = provided_arg.value {
@@ -363,13 +952,18 @@ impl Validator {
                     }
                 }
 
-                if !arg.arg_types.contains(&provided_arg.arg_type) && !arg.arg_types.contains(&ArgType::ANY) && provided_arg.arg_type != ArgType::VARIABLE {
+                // variables with a known type are checked like values, unless the argument takes the variable itself
+                let provided_type = match self.variable_type(&provided_arg) {
+                    Some(variable_type) if !arg.arg_types.contains(&ArgType::VARIABLE) => variable_type,
+                    _ => provided_arg.arg_type.clone()
+                };
+                if !arg.arg_types.contains(&provided_type) && !arg.arg_types.contains(&ArgType::ANY) && provided_type != ArgType::VARIABLE {
                     if arg.allow_multiple && matched_one {
                         node_args.insert(0, provided_arg);
                         index -= 1;
                         break;
                     }
-                    return Err(ValidateError::WrongArgumentType { args: all_provided_args, index, name: arg.name, expected_types: arg.arg_types, found_type: provided_arg.arg_type })
+                    return Err(ValidateError::WrongArgumentType { args: all_provided_args, index, name: arg.name.clone(), expected_types: arg.arg_types.clone(), found_type: provided_type })
                 }
 
                 provided_arg.index = index;
@@ -381,72 +975,430 @@ impl Validator {
         if !node_args.is_empty() {
             for val in node_args.clone() {
                 if val.arg_type != ArgType::TAG {
-                    return Err(ValidateError::TooManyArguments { name: action.dfrs_name.clone(), start_pos, end_pos })
+                    return Err(ValidateError::TooManyArguments { name: name.to_owned(), start_pos, end_pos })
                 }
                 tags.push(val)
             }
         }
 
-        for given_tag in tags.clone() {
-            match given_tag.value {
-                ArgValue::Tag { tag: tag_name, value: _, definition: _, name_end_pos, value_start_pos: _ } => {
-                    let mut found = false;
-                    let mut available = vec![];
-                    for tag in action.tags.clone() {
-                        available.push(tag.dfrs_name.clone());
-                        if tag.dfrs_name == tag_name {
-                            found = true;
-                        }
-                    }
-                    if !found {
-                        return Err(ValidateError::UnknownTag { tag_name, available, start_pos: given_tag.start_pos, end_pos: name_end_pos });
-                    }
-                }
-                _ => unreachable!()
+        Ok((args, tags))
+    }
+}
+
+/// Formats an overload like a function signature, `(name?: [NUMBER], values*: [TEXT])`
+fn signature(args: &[DefinedArg]) -> String {
+    let args: Vec<String> = args.iter().map(|arg| {
+        let optional = if arg.optional { "?" } else { "" };
+        let multiple = if arg.allow_multiple { "*" } else { "" };
+        format!("{}{optional}{multiple}: {:?}", arg.name, arg.arg_types)
+    }).collect();
+    format!("({})", args.join(", "))
+}
+
+/// Collects the operands of a chain of the same operator, `a - b - c` becomes `[a, b, c]`
+fn flatten_math(node: MathNode, operator: &MathOperator) -> Vec<MathNode> {
+    match node {
+        MathNode::Binary { operator: op, left, right } if op == *operator => {
+            let mut operands = flatten_math(*left, operator);
+            if matches!(operator, MathOperator::Add | MathOperator::Mul) {
+                operands.append(&mut flatten_math(*right, operator));
+            } else {
+                operands.push(*right);
             }
+            operands
         }
+        node => vec![node]
+    }
+}
 
-        for tag in action.tags.clone() {
-            let mut matched = false;
-            for given_tag in tags.clone() {
-                match given_tag.value {
-                    ArgValue::Tag { tag: tag_name, value, name_end_pos, value_start_pos , ..} => {
-                        let actual = match value.clone().as_ref() {
-                            ArgValue::Text { text } => text.clone(),
-                            err => return Err(ValidateError::InvalidTagOption { tag_name, provided: format!("{err:?}"), options: tag.options, start_pos: value_start_pos, end_pos: given_tag.end_pos })
-                        };
-                        if tag.dfrs_name == tag_name {
-                            if tag.options.contains(&actual) {
-                                matched = true;
-                                args.push(Arg {
-                                    arg_type: ArgType::TAG,
-                                    value: ArgValue::Tag { tag: tag.df_name.clone(), value, definition: Some(tag.clone()), name_end_pos, value_start_pos },
-                                    index: tag.slot as i32,
-                                    start_pos: given_tag.start_pos,
-                                    end_pos: given_tag.end_pos
-                                });
-                            } else {
-                                return Err(ValidateError::InvalidTagOption { tag_name, provided: actual, options: tag.options, start_pos: value_start_pos, end_pos: given_tag.end_pos });
-                            }
-                        }
-                    }
-                    _ => unreachable!()
+/// The values used in a math expression
+fn math_args(node: &MathNode) -> Vec<Arg> {
+    match node {
+        MathNode::Value { arg } => vec![arg.clone()],
+        MathNode::Binary { left, right, .. } => [math_args(left), math_args(right)].concat()
+    }
+}
+
+/// Builds a %math expression if every operand is a number or variable
+fn math_text(node: &MathNode) -> Option<String> {
+    match node {
+        MathNode::Value { arg } => match &arg.value {
+            ArgValue::Number { number } => Some(number.to_string()),
+            ArgValue::ComplexNumber { number } if is_math_atom(number) => Some(number.clone()),
+            ArgValue::ComplexNumber { number } => Some(format!("({number})")),
+            ArgValue::Variable { name, .. } => Some(format!("%var({name})")),
+            _ => None
+        },
+        MathNode::Binary { operator, left, right } => {
+            let operator = match operator {
+                MathOperator::Add => "+",
+                MathOperator::Sub => "-",
+                MathOperator::Mul => "*",
+                MathOperator::Div => "/"
+            };
+            let wrap = |node: &MathNode| -> Option<String> {
+                match node {
+                    MathNode::Binary { .. } => Some(format!("({})", math_text(node)?)),
+                    MathNode::Value { .. } => math_text(node)
+                }
+            };
+            Some(format!("{}{}{}", wrap(left)?, operator, wrap(right)?))
+        }
+    }
+}
+
+/// Keeps the inferred types both maps agree on
+fn merge_types(mut types: HashMap<(String, String), ArgType>, other: &HashMap<(String, String), ArgType>) -> HashMap<(String, String), ArgType> {
+    types.retain(|key, value| other.get(key) == Some(value));
+    types
+}
+
+fn selector_name(selector: &Selector) -> &'static str {
+    SELECTORS.entries().find(|entry| entry.1 == selector).map(|entry| *entry.0).unwrap_or_default()
+}
+
+/// Whether the text is a number or a single placeholder like `%var(a)`, which don't need parentheses inside %math
+fn is_math_atom(text: &str) -> bool {
+    if text.parse::<f64>().is_ok() {
+        return true
+    }
+    let Some(open) = text.find('(') else {
+        return false
+    };
+    if !text.starts_with('%') || !text[1..open].chars().all(|char| char.is_ascii_alphanumeric()) {
+        return false
+    }
+    let mut depth = 0;
+    for (index, char) in text.char_indices().skip(open) {
+        match char {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return index == text.len() - 1
                 }
             }
-            if !matched {
-                let data = Box::new(ArgValue::Text {text:tag.default.clone()});
-                args.push(Arg {
-                    arg_type: ArgType::TAG,
-                    value: ArgValue::Tag { tag: tag.df_name.clone(), value: data, definition: Some(tag.clone()), name_end_pos: Position::new(0, 0), value_start_pos: Position::new(0, 0) },
-                    index: tag.slot as i32,
-                    start_pos: Position::new(0, 0),
-                    end_pos: Position::new(0, 0)
-                });
+            _ => {}
+        }
+    }
+    false
+}
+
+/// The processes declared by each `.dfrs` file of a project, read once and shared by every file that gets validated
+#[derive(Debug, Default, PartialEq)]
+pub struct ProjectProcesses {
+    /// Processes by file name, None if the file could not be parsed
+    files: HashMap<String, Option<Vec<String>>>
+}
+
+impl ProjectProcesses {
+    pub fn scan(dir: &Path) -> ProjectProcesses {
+        let mut project = ProjectProcesses::default();
+        let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+        let Ok(entries) = fs::read_dir(dir) else {
+            return project
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_none_or(|extension| extension != "dfrs") {
+                continue;
             }
+            let Ok(data) = fs::read_to_string(&path) else { continue };
+            let node = Lexer::new(data).run().ok().and_then(|tokens| Parser::new(tokens).run().ok());
+            project.update(&path, node.as_ref());
         }
+        project
+    }
 
-        Ok(args)
+    /// Replaces the processes of a file after it changed, `None` if it could not be parsed
+    pub fn update(&mut self, file: &Path, node: Option<&FileNode>) {
+        let processes = node.map(|node| node.processes.iter().map(|process| process.name.clone()).collect());
+        self.files.insert(file_name(file), processes);
+    }
+
+    /// The processes declared in the other files and the names of the other files that could not be parsed
+    pub fn for_file(&self, file: &Path) -> (Vec<String>, Vec<String>) {
+        let file = file_name(file);
+        let mut processes = vec![];
+        let mut unparsed = vec![];
+        for (name, file_processes) in &self.files {
+            if *name == file {
+                continue;
+            }
+            match file_processes {
+                Some(file_processes) => processes.extend(file_processes.iter().cloned()),
+                None => unparsed.push(name.clone())
+            }
+        }
+        unparsed.sort();
+        (processes, unparsed)
+    }
+}
+
+fn file_name(file: &Path) -> String {
+    file.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+fn declared_type(node: &VariableNode) -> Option<ArgType> {
+    let value_type = ArgType::from(node.value_type.as_ref()?);
+    if value_type == ArgType::ANY || value_type == ArgType::VARIABLE {
+        return None
     }
+    Some(value_type)
 }
 
-// TODO validate potions, sounds, particles etc
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate_source(source: &str) -> FileNode {
+        let tokens = Lexer::new(source.into()).run().unwrap_or_else(|_| panic!("Failed to lex"));
+        let node = Parser::new(tokens).run().unwrap_or_else(|errors| panic!("Failed to parse: {errors:?}"));
+        match Validator::new().validate(node) {
+            Ok(node) => node,
+            Err(errors) => {
+                let messages: Vec<String> = errors.into_iter().map(|err| Diagnostic::from(err).message).collect();
+                panic!("Failed to validate: {messages:?}")
+            }
+        }
+    }
+
+    fn validate_errors(source: &str) -> Vec<Diagnostic> {
+        let tokens = Lexer::new(source.into()).run().unwrap_or_else(|_| panic!("Failed to lex"));
+        let node = Parser::new(tokens).run().unwrap_or_else(|errors| panic!("Failed to parse: {errors:?}"));
+        match Validator::new().validate(node) {
+            Ok(_) => panic!("Expected validation to fail"),
+            Err(errors) => errors.into_iter().map(Diagnostic::from).collect()
+        }
+    }
+
+    fn validate_warnings(source: &str) -> Vec<Diagnostic> {
+        let tokens = Lexer::new(source.into()).run().unwrap_or_else(|_| panic!("Failed to lex"));
+        let node = Parser::new(tokens).run().unwrap_or_else(|errors| panic!("Failed to parse: {errors:?}"));
+        let mut validator = Validator::new();
+        if validator.validate(node).is_err() {
+            panic!("Failed to validate")
+        }
+        validator.take_warnings().into_iter().map(Diagnostic::from).collect()
+    }
+
+    /// The actions of the first event, with assignments and groups expanded
+    fn event_actions(node: &FileNode) -> Vec<ActionNode> {
+        fn collect(expressions: &[ExpressionNode], actions: &mut Vec<ActionNode>) {
+            for expression in expressions {
+                match &expression.node {
+                    Expression::Action { node } => actions.push(node.clone()),
+                    Expression::Assign { node } => actions.extend(node.actions.clone()),
+                    Expression::Group { node } => collect(&node.expressions, actions),
+                    _ => {}
+                }
+            }
+        }
+        let mut actions = vec![];
+        collect(&node.events[0].expressions, &mut actions);
+        actions
+    }
+
+    fn arg_text(arg: &Arg) -> String {
+        match &arg.value {
+            ArgValue::Number { number } => number.to_string(),
+            ArgValue::ComplexNumber { number } => number.clone(),
+            ArgValue::Variable { name, .. } => format!("var {name}"),
+            ArgValue::GameValue { dfrs_name, .. } => format!("${dfrs_name}"),
+            ArgValue::Condition { name, args, .. } => format!("{name}({})", args_text(args)),
+            other => format!("{other:?}")
+        }
+    }
+
+    fn args_text(args: &[Arg]) -> String {
+        args.iter().filter(|arg| arg.arg_type != ArgType::EMPTY).map(arg_text).collect::<Vec<String>>().join(", ")
+    }
+
+    /// A compact outline of the lowered expressions, like `=(var x, 1); if !=(var x, 2) { ... } else { ... }`
+    fn describe(expressions: &[ExpressionNode]) -> String {
+        let mut parts = vec![];
+        for expression in expressions {
+            match &expression.node {
+                Expression::Action { node } => parts.push(format!("{}({})", node.name, args_text(&node.args))),
+                Expression::Assign { node } => parts.extend(node.actions.iter().map(|action| format!("{}({})", action.name, args_text(&action.args)))),
+                Expression::Group { node } => parts.push(describe(&node.expressions)),
+                Expression::Conditional { node } => {
+                    let mut part = format!("if {}{}({}) {{ {} }}", if node.inverted { "not " } else { "" }, node.name, args_text(&node.args), describe(&node.expressions));
+                    if !node.else_expressions.is_empty() {
+                        part.push_str(&format!(" else {{ {} }}", describe(&node.else_expressions)));
+                    }
+                    parts.push(part);
+                }
+                Expression::Repeat { node } => parts.push(format!("repeat {}({}) {{ {} }}", node.name, args_text(&node.args), describe(&node.expressions))),
+                Expression::Variable { .. } => {}
+                other => parts.push(format!("{other:?}"))
+            }
+        }
+        parts.join("; ")
+    }
+
+    fn describe_event(source: &str) -> String {
+        describe(&validate_source(source).events[0].expressions)
+    }
+
+    #[test]
+    fn complex_number_operands_keep_precedence() {
+        let node = validate_source("@join {\n  line a;\n  line x;\n  x = 2 * Number(\"%var(a)+1\");\n  x = 2 * Number(\"%var(a)\");\n}");
+        let actions = event_actions(&node);
+        assert_eq!(actions.len(), 2);
+        assert_eq!(actions[0].name, "=");
+        assert_eq!(arg_text(&actions[0].args[1]), "%math(2*(%var(a)+1))");
+        assert_eq!(arg_text(&actions[1].args[1]), "%math(2*%var(a))");
+    }
+
+    #[test]
+    fn particle_fields_out_of_range_are_rejected() {
+        let errors = validate_errors("@join {\n  p.particle(Particle(\"Dust\", 1, 0, 0, rgb=99999999), Location(0,0,0));\n}");
+        assert_eq!(errors[0].code, "E0215");
+        assert_eq!(errors[0].help.as_deref(), Some("expected a value from 0 to 16777215"));
+    }
+
+    #[test]
+    fn inferred_types_do_not_leak_out_of_branches() {
+        validate_source("@join {\n  line a;\n  line k;\n  line n;\n  line i;\n  a = 1;\n  if a == 1 {\n    v.string(k, 'a');\n  } else {\n    k = 5;\n  }\n  v.add(n, k);\n  repeat multiple(i, 3) {\n    v.add(n, n);\n    v.string(n, 'a');\n  }\n  v.add(a, n);\n}");
+    }
+
+    #[test]
+    fn inferred_types_both_branches_agree_on_are_kept() {
+        let errors = validate_errors("@join {\n  line a;\n  line k;\n  line n;\n  a = 1;\n  if a == 1 {\n    v.string(k, 'a');\n  } else {\n    v.string(k, 'b');\n  }\n  v.add(n, k);\n}");
+        assert_eq!(errors[0].code, "E0205");
+    }
+
+    #[test]
+    fn project_processes_exclude_the_file_and_list_unparsed_files() {
+        let tokens = Lexer::new("proc worker {\n}".into()).run().unwrap_or_else(|_| panic!("Failed to lex"));
+        let node = Parser::new(tokens).run().unwrap_or_else(|errors| panic!("Failed to parse: {errors:?}"));
+        let mut project = ProjectProcesses::default();
+        project.update(Path::new("a.dfrs"), None);
+        project.update(Path::new("b.dfrs"), Some(&node));
+        project.update(Path::new("c.dfrs"), None);
+
+        assert_eq!(project.for_file(Path::new("a.dfrs")), (vec!["worker".to_owned()], vec!["c.dfrs".to_owned()]));
+        assert_eq!(project.for_file(Path::new("b.dfrs")), (vec![], vec!["a.dfrs".to_owned(), "c.dfrs".to_owned()]));
+    }
+
+    #[test]
+    fn value_names_are_matched_ignoring_case() {
+        let node = validate_source("@join {\n  p.givePotion(Potion(\"jump boost\", 1, 2));\n}");
+        let actions = event_actions(&node);
+        assert!(matches!(&actions[0].args[0].value, ArgValue::Potion { potion, .. } if potion == "Jump Boost"));
+    }
+
+    #[test]
+    fn math_assignments_lower_to_set_variable_actions() {
+        let outline = describe_event("@join {\n  line a;\n  line b;\n  line x;\n  x = a + 2;\n  x = a + 2 * b;\n  x = (a + 2) * b - 1;\n  x = $currentHealth * 2 + a;\n}");
+        assert_eq!(outline, "=(var x, %math(%var(a)+2)); =(var x, %math(%var(a)+(2*%var(b)))); =(var x, %math(((%var(a)+2)*%var(b))-1)); x(var __dfrs_tmp1, $currentHealth, 2); +(var x, var __dfrs_tmp1, var a)");
+    }
+
+    #[test]
+    fn comparisons_lower_to_if_variable_actions() {
+        let outline = describe_event("@join {\n  line a;\n  if a >= 5 {\n    a = 1;\n  }\n  if a != 5 {\n  }\n  if a < 2 {\n  }\n}");
+        assert_eq!(outline, "if >=(var a, 5) { =(var a, 1) }; if !=(var a, 5) {  }; if <(var a, 2) {  }");
+    }
+
+    #[test]
+    fn else_if_chains_nest_in_else_blocks() {
+        let outline = describe_event("@join {\n  line a;\n  if a == 1 {\n    a = 10;\n  } else if a == 2 {\n    a = 20;\n  } else {\n    a = 30;\n  }\n}");
+        assert_eq!(outline, "if =(var a, 1) { =(var a, 10) } else { if =(var a, 2) { =(var a, 20) } else { =(var a, 30) } }");
+    }
+
+    #[test]
+    fn and_nests_conditionals() {
+        let outline = describe_event("@join {\n  line a;\n  line b;\n  if a == 1 && b == 2 {\n    a = 3;\n  }\n}");
+        assert_eq!(outline, "if =(var a, 1) { if =(var b, 2) { =(var a, 3) } }");
+    }
+
+    #[test]
+    fn or_sets_a_flag_variable() {
+        let outline = describe_event("@join {\n  line a;\n  line b;\n  if a == 1 || b == 2 {\n    a = 4;\n  } else {\n    a = 5;\n  }\n}");
+        assert_eq!(outline, "=(var __dfrs_flag1, 0); if =(var a, 1) { =(var __dfrs_flag1, 1) }; if =(var b, 2) { =(var __dfrs_flag1, 1) }; if =(var __dfrs_flag1, 1) { =(var a, 4) } else { =(var a, 5) }");
+    }
+
+    #[test]
+    fn repeat_while_with_or_updates_the_flag_every_iteration() {
+        let outline = describe_event("@join {\n  line a;\n  line b;\n  repeat while(a < 10 || b < 10) {\n    a = a + 1;\n  }\n}");
+        assert_eq!(outline, "=(var __dfrs_flag1, 0); if <(var a, 10) { =(var __dfrs_flag1, 1) }; if <(var b, 10) { =(var __dfrs_flag1, 1) }; repeat While(=(var __dfrs_flag1, 1)) { =(var a, %math(%var(a)+1)); =(var __dfrs_flag1, 0); if <(var a, 10) { =(var __dfrs_flag1, 1) }; if <(var b, 10) { =(var __dfrs_flag1, 1) } }");
+    }
+
+    #[test]
+    fn overloads_are_picked_by_argument_types() {
+        let node = validate_source("@join {\n  p.setHealth(5);\n  p.setHealth(Location(0, 0, 0));\n}");
+        let actions = event_actions(&node);
+        assert_eq!(actions[0].args[0].arg_type, ArgType::NUMBER);
+        assert_eq!(actions[1].args[0].arg_type, ArgType::LOCATION);
+
+        let errors = validate_errors("@join {\n  p.setHealth('a');\n}");
+        assert_eq!(errors[0].code, "E0212");
+    }
+    #[test]
+    fn only_cancellable_events_can_be_cancelled() {
+        let errors = validate_errors("@join! {\n}");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, "E0216");
+        let node = validate_source("@killPlayer! {\n}");
+        assert!(node.events[0].cancelled);
+        assert_eq!(node.events[0].event, "KillPlayer");
+    }
+    #[test]
+    fn selectors_the_event_does_not_provide_are_warned_about() {
+        let warnings = validate_warnings("@join {\n  p:killer.sendMessage(\"a\");\n}");
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, "W0201");
+        assert_eq!(warnings[0].span.as_ref().map(|span| span.start.line), Some(2));
+        assert!(validate_warnings("@killPlayer {\n  p:victim.sendMessage(\"a\");\n}").is_empty());
+    }
+    #[test]
+    fn game_value_selectors_the_event_does_not_provide_are_warned_about() {
+        let warnings = validate_warnings("@join {\n  p.sendMessage($killer:name);\n}");
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, "W0201");
+        assert!(validate_warnings("@killPlayer {\n  p.sendMessage($killer:name);\n}").is_empty());
+    }
+    #[test]
+    fn direct_calls_resolve_to_the_df_name_of_the_function() {
+        let node = validate_source("fn greet = `Greet Player`() {\n}\n@join {\n  greet();\n}");
+        let Expression::Call { node } = &node.events[0].expressions[0].node else { panic!("Expected a call") };
+        assert_eq!(node.name, "Greet Player");
+        let errors = validate_errors("fn greet() {\n}\n@join {\n  gret();\n}");
+        assert_eq!(errors[0].code, "E0209");
+        assert_eq!(errors[0].help.as_deref(), Some("a function with a similar name exists: 'greet'"));
+    }
+    #[test]
+    fn direct_call_arguments_are_checked_against_the_parameters() {
+        let source = |call: &str| format!("fn teleport(target: location, delay?: number) {{\n}}\n@join {{\n  {call};\n}}");
+        validate_source(&source("teleport(Location(0, 0, 0))"));
+        validate_source(&source("teleport(Location(0, 0, 0), 5)"));
+        assert_eq!(validate_errors(&source("teleport()"))[0].code, "E0204");
+        assert_eq!(validate_errors(&source("teleport(5)"))[0].code, "E0205");
+        assert_eq!(validate_errors(&source("teleport(Location(0, 0, 0), 5, 6)"))[0].code, "E0206");
+    }
+    #[test]
+    fn type_mismatches_point_at_the_declaration() {
+        let errors = validate_errors("@join {\n  line count: number;\n  v.equal(count, \"a\");\n}");
+        assert_eq!(errors[0].code, "E0211");
+        let label = &errors[0].labels[0];
+        assert_eq!((label.span.start.line, label.span.end.line), (2, 2));
+        assert_eq!(label.message, "declared as NUMBER here");
+    }
+
+    #[test]
+    fn call_argument_errors_point_at_the_function() {
+        let errors = validate_errors("fn teleport(target: location) {\n}\n@join {\n  teleport(5);\n}");
+        assert_eq!(errors[0].code, "E0205");
+        let label = &errors[0].labels[0];
+        assert_eq!(label.span.start.line, 1);
+        assert_eq!(label.message, "'teleport' is defined here");
+    }
+    #[test]
+    fn argument_errors_cover_the_whole_call() {
+        let errors = validate_errors("@join {\n  p.teleport(\n\n  );\n}");
+        assert_eq!(errors[0].code, "E0204");
+        let span = errors[0].span.as_ref().expect("Missing span");
+        assert_eq!((span.start.line, span.end.line), (2, 4));
+    }
+}
\ No newline at end of file